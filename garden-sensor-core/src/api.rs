@@ -0,0 +1,154 @@
+//! JSON documents of the settings portal REST API. Settings are keyed by
+//! their [`MAP_NVS_FORM`](crate::configuration::main_configuration::MAP_NVS_FORM)
+//! form field names, so every field of the form is covered.
+
+use serde_json::{json, Map, Value};
+
+use crate::configuration::config_store::ConfigStore;
+use crate::configuration::main_configuration::{self, FormErrors, MapFormValue, MAP_NVS_FORM};
+use crate::configuration::nvs_configuration::NvsConfiguration;
+use crate::payload::generate_json;
+use crate::sensors::sensor::{endpoints, SensorsVec};
+use crate::string_error::StringError;
+use crate::template::AccessPoint;
+
+/// Every setting but secrets (passwords), `id` is an hexadecimal string as in
+/// the form.
+pub fn config_json<S: ConfigStore>(config: &NvsConfiguration<S>) -> Value {
+    let mut map = Map::new();
+
+    for elem in MAP_NVS_FORM.iter().filter(|e| !e.is_secret()) {
+        let value = match main_configuration::read_form_value(config, elem) {
+            MapFormValue::String(s) => json!(s),
+            MapFormValue::Float(v) => json!(v),
+            v @ MapFormValue::U32Hex(_) => json!(v.to_string()),
+            MapFormValue::Unsigned32(v) => json!(v),
+            MapFormValue::Unsigned64(v) => json!(v),
+            MapFormValue::Unsigned8(v) => json!(v),
+        };
+
+        map.insert(elem.form_name.to_string(), value);
+    }
+
+    Value::Object(map)
+}
+
+/// Apply a JSON object of settings, see
+/// [`apply_json_fields`](main_configuration::apply_json_fields).
+pub fn apply_config<S: ConfigStore>(
+    config: &mut NvsConfiguration<S>,
+    body: &str,
+) -> anyhow::Result<usize> {
+    match serde_json::from_str::<Value>(body) {
+        Ok(Value::Object(fields)) => main_configuration::apply_json_fields(config, &fields),
+        _ => Err(StringError("Body must be a JSON object").into()),
+    }
+}
+
+/// Current readings, keyed by endpoint, as they would be uploaded.
+pub fn sensors_json(id: u32, name: &str, sensors: &mut SensorsVec) -> Value {
+    let mut map = Map::new();
+
+    for endpoint in endpoints(sensors) {
+        map.insert(
+            endpoint.to_string(),
+            generate_json(id, name, sensors, endpoint, None),
+        );
+    }
+
+    Value::Object(map)
+}
+
+pub fn access_points_json(aps: &[AccessPoint]) -> Value {
+    Value::Array(
+        aps.iter()
+            .map(|ap| json!({ "ssid": ap.ssid, "rssi": ap.rssi }))
+            .collect(),
+    )
+}
+
+pub fn updated_json(count: usize) -> Value {
+    json!({ "updated": count })
+}
+
+pub fn error_json(message: &str) -> Value {
+    json!({ "error": message })
+}
+
+/// Error of a settings update, invalid fields are listed in `fields`.
+pub fn settings_error_json(error: &anyhow::Error) -> Value {
+    match error.downcast_ref::<FormErrors>() {
+        Some(errors) => {
+            let fields: Map<String, Value> = errors
+                .0
+                .iter()
+                .map(|e| (e.field.clone(), json!(e.message)))
+                .collect();
+
+            json!({ "error": "Invalid settings", "fields": fields })
+        }
+        None => error_json(&error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::nvs_configuration::mock::config;
+    use crate::configuration::nvs_configuration::KEY_PASSPHRASE;
+
+    #[test]
+    fn config_without_secrets() {
+        let mut config = config();
+        config.store_string(KEY_PASSPHRASE, "hunter22", 63).unwrap();
+
+        let json = config_json(&config);
+
+        assert_eq!(json["sleep"], json!(3_600_000_000u64));
+        assert_eq!(json["id"], json!("0"));
+        assert!(json.get("pass").is_none());
+        assert!(!json.to_string().contains("hunter22"));
+    }
+
+    #[test]
+    fn apply_then_read_config() {
+        let mut config = config();
+
+        let count = apply_config(
+            &mut config,
+            r#"{"name": "Tomatoes", "id": "ab12", "sleep": 600000000, "pass": "secret"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(count, 4);
+        assert_eq!(config.get_passphrase(), "secret");
+
+        let json = config_json(&config);
+        assert_eq!(json["name"], json!("Tomatoes"));
+        assert_eq!(json["id"], json!("ab12"));
+        assert_eq!(json["sleep"], json!(600_000_000));
+    }
+
+    #[test]
+    fn invalid_config_rejected() {
+        let mut config = config();
+
+        let error =
+            apply_config(&mut config, r#"{"name": "Tomatoes", "sleep": 1, "x": 0}"#).unwrap_err();
+
+        assert_eq!(
+            settings_error_json(&error),
+            json!({
+                "error": "Invalid settings",
+                "fields": {"sleep": "Value out of range", "x": "Unknown settings field"},
+            })
+        );
+        assert_eq!(config.get_name(), "");
+
+        let error = apply_config(&mut config, "[]").unwrap_err();
+        assert_eq!(
+            settings_error_json(&error),
+            json!({"error": "Body must be a JSON object"})
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! HTTP basic authentication of the settings portal.
+
+/// User name of the settings portal login
+pub const ADMIN_USER: &str = "admin";
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// `true` when the `Authorization` header value holds the admin credentials,
+/// never while no password is set.
+pub fn is_authorized(authorization: Option<&str>, password: &str) -> bool {
+    if password.is_empty() {
+        return false;
+    }
+
+    let credentials = match authorization.and_then(|h| h.trim().strip_prefix("Basic ")) {
+        Some(credentials) => credentials.trim(),
+        None => return false,
+    };
+
+    let expected = base64_encode(format!("{}:{}", ADMIN_USER, password).as_bytes());
+
+    constant_time_eq(credentials.as_bytes(), expected.as_bytes())
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut result = String::with_capacity((data.len() + 2) / 3 * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+/// Compare without returning early, so the time taken does not tell how much
+/// of a guessed password is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foob"), "Zm9vYg==");
+        assert_eq!(base64_encode(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn correct_password() {
+        // admin:secret
+        assert!(is_authorized(Some("Basic YWRtaW46c2VjcmV0"), "secret"));
+        assert!(is_authorized(Some(" Basic  YWRtaW46c2VjcmV0 "), "secret"));
+    }
+
+    #[test]
+    fn wrong_credentials() {
+        assert!(!is_authorized(Some("Basic YWRtaW46c2VjcmV0"), "secreT"));
+        // admin:secre, then root:secret
+        assert!(!is_authorized(Some("Basic YWRtaW46c2VjcmU="), "secret"));
+        assert!(!is_authorized(Some("Basic cm9vdDpzZWNyZXQ="), "secret"));
+        assert!(!is_authorized(Some("Basic "), "secret"));
+    }
+
+    #[test]
+    fn missing_header_or_wrong_scheme() {
+        assert!(!is_authorized(None, "secret"));
+        assert!(!is_authorized(Some(""), "secret"));
+        assert!(!is_authorized(Some("YWRtaW46c2VjcmV0"), "secret"));
+        assert!(!is_authorized(Some("Bearer YWRtaW46c2VjcmV0"), "secret"));
+        assert!(!is_authorized(Some("basic YWRtaW46c2VjcmV0"), "secret"));
+    }
+
+    #[test]
+    fn empty_password_never_matches() {
+        // admin:
+        assert!(!is_authorized(Some("Basic YWRtaW46"), ""));
+        assert!(!is_authorized(None, ""));
+    }
+}
@@ -0,0 +1,201 @@
+//! DNS answers of the captive portal: every `A` query resolves to the access
+//! point address, so any name typed or probed by a client reaches the
+//! settings page.
+
+const HEADER_LEN: usize = 12;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const FLAG_RECURSION_AVAILABLE: u16 = 0x0080;
+const OPCODE_MASK: u16 = 0x7800;
+const RCODE_NOT_IMPLEMENTED: u16 = 4;
+
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+/// Time to live of the answers, short so clients forget them once the device
+/// leaves settings mode
+const TTL_SECS: u32 = 60;
+
+/// Response to the DNS `query`, `None` if it is not a query worth answering.
+/// Only the first question is answered, with `address` for `A` queries and no
+/// record otherwise (e.g. `AAAA`, so the client falls back to IPv4).
+pub fn response(query: &[u8], address: [u8; 4]) -> Option<Vec<u8>> {
+    if query.len() < HEADER_LEN {
+        return None;
+    }
+
+    let flags = read_u16(query, 2)?;
+    let questions = read_u16(query, 4)?;
+
+    if flags & FLAG_RESPONSE != 0 || questions == 0 {
+        return None;
+    }
+
+    let mut response = Vec::with_capacity(query.len() + 16);
+    response.extend_from_slice(&query[0..2]);
+
+    let reply_flags = FLAG_RESPONSE
+        | FLAG_AUTHORITATIVE
+        | FLAG_RECURSION_AVAILABLE
+        | (flags & (OPCODE_MASK | FLAG_RECURSION_DESIRED));
+
+    // Only standard queries are answered
+    if flags & OPCODE_MASK != 0 {
+        response.extend_from_slice(&(reply_flags | RCODE_NOT_IMPLEMENTED).to_be_bytes());
+        response.extend_from_slice(&[0; 8]);
+        return Some(response);
+    }
+
+    let question_end = question_end(query)?;
+    let qtype = read_u16(query, question_end - 4)?;
+    let qclass = read_u16(query, question_end - 2)?;
+    let answer = (qtype == TYPE_A || qtype == TYPE_ANY) && qclass == CLASS_IN;
+
+    response.extend_from_slice(&reply_flags.to_be_bytes());
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&(answer as u16).to_be_bytes());
+    response.extend_from_slice(&[0; 4]);
+    response.extend_from_slice(&query[HEADER_LEN..question_end]);
+
+    if answer {
+        // Name compressed as a pointer to the question
+        response.extend_from_slice(&[0xc0, HEADER_LEN as u8]);
+        response.extend_from_slice(&TYPE_A.to_be_bytes());
+        response.extend_from_slice(&CLASS_IN.to_be_bytes());
+        response.extend_from_slice(&TTL_SECS.to_be_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&address);
+    }
+
+    Some(response)
+}
+
+/// Offset following the first question (name, type and class)
+fn question_end(query: &[u8]) -> Option<usize> {
+    let mut offset = HEADER_LEN;
+
+    loop {
+        let len = *query.get(offset)? as usize;
+
+        match len {
+            0 => break,
+            // Compressed names are not expected in a question
+            l if l & 0xc0 != 0 => return None,
+            l => offset += l + 1,
+        }
+    }
+
+    let end = offset + 1 + 4;
+
+    (end <= query.len()).then_some(end)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *data.get(offset)?,
+        *data.get(offset + 1)?,
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: [u8; 4] = [192, 168, 71, 1];
+    const TYPE_AAAA: u16 = 28;
+
+    fn query(flags: u16, questions: u16, name: &str, qtype: u16) -> Vec<u8> {
+        let mut query = vec![0x12, 0x34];
+        query.extend_from_slice(&flags.to_be_bytes());
+        query.extend_from_slice(&questions.to_be_bytes());
+        query.extend_from_slice(&[0; 6]);
+
+        for label in name.split('.') {
+            query.push(label.len() as u8);
+            query.extend_from_slice(label.as_bytes());
+        }
+        query.push(0);
+        query.extend_from_slice(&qtype.to_be_bytes());
+        query.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+        query
+    }
+
+    #[test]
+    fn answers_a_query() {
+        let query = query(FLAG_RECURSION_DESIRED, 1, "example.com", TYPE_A);
+        let response = response(&query, ADDRESS).unwrap();
+
+        assert_eq!(&response[0..2], &[0x12, 0x34]);
+        assert_eq!(read_u16(&response, 2), Some(0x8580));
+        assert_eq!(read_u16(&response, 4), Some(1));
+        assert_eq!(read_u16(&response, 6), Some(1));
+        assert_eq!(&response[8..HEADER_LEN], &[0; 4]);
+        assert_eq!(&response[HEADER_LEN..query.len()], &query[HEADER_LEN..]);
+        assert_eq!(
+            &response[query.len()..],
+            &[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 168, 71, 1]
+        );
+    }
+
+    #[test]
+    fn other_types_get_no_record() {
+        for qtype in [TYPE_AAAA, 15, 16] {
+            let query = query(0, 1, "example.com", qtype);
+            let response = response(&query, ADDRESS).unwrap();
+
+            assert_eq!(response.len(), query.len());
+            assert_eq!(read_u16(&response, 6), Some(0));
+            assert_eq!(&response[HEADER_LEN..], &query[HEADER_LEN..]);
+        }
+
+        let any = response(&query(0, 1, "example.com", TYPE_ANY), ADDRESS).unwrap();
+        assert_eq!(read_u16(&any, 6), Some(1));
+    }
+
+    #[test]
+    fn truncated_queries() {
+        let query = query(0, 1, "example.com", TYPE_A);
+
+        assert_eq!(response(&query[..HEADER_LEN - 1], ADDRESS), None);
+        assert_eq!(response(&query[..HEADER_LEN], ADDRESS), None);
+        // Cut inside the name, then inside the type and class
+        assert_eq!(response(&query[..HEADER_LEN + 5], ADDRESS), None);
+        assert_eq!(response(&query[..query.len() - 1], ADDRESS), None);
+    }
+
+    #[test]
+    fn rejects_compressed_and_long_labels() {
+        let mut pointer = query(0, 1, "example.com", TYPE_A);
+        pointer[HEADER_LEN] = 0xc0;
+        pointer[HEADER_LEN + 1] = HEADER_LEN as u8;
+        assert_eq!(response(&pointer, ADDRESS), None);
+
+        let long = query(0, 1, &"a".repeat(64), TYPE_A);
+        assert_eq!(response(&long, ADDRESS), None);
+    }
+
+    #[test]
+    fn other_opcodes_are_not_implemented() {
+        // Opcode 5 (UPDATE)
+        let flags = 5 << 11;
+        let query = query(flags, 1, "example.com", TYPE_A);
+        let response = response(&query, ADDRESS).unwrap();
+
+        assert_eq!(response.len(), HEADER_LEN);
+        assert_eq!(read_u16(&response, 2), Some(0x8480 | flags | 4));
+        assert_eq!(&response[4..], &[0; 8]);
+    }
+
+    #[test]
+    fn ignores_empty_queries_and_responses() {
+        assert_eq!(response(&query(0, 0, "example.com", TYPE_A), ADDRESS), None);
+        assert_eq!(
+            response(&query(FLAG_RESPONSE, 1, "example.com", TYPE_A), ADDRESS),
+            None
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use serde_json::Value;
+
+/// JSON fields compared with [`ReportThresholds::delta`], all percentages.
+/// Other values (e.g. a water distance in mm, a temperature) do not trigger a
+/// report, the level derived from them does.
+const DELTA_FIELDS: &[&str] = &["level", "humidity"];
+
+const BATTERY_FIELD: &str = "battery";
+
+/// When a reading is worth sending
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReportThresholds {
+    /// Smallest change of a level or humidity to report, in percentage
+    /// points, 0 reports every reading
+    pub delta: f32,
+    /// Smallest battery level drop to report
+    pub battery_delta: f32,
+    /// Report anyway after this many wake-ups without report
+    pub heartbeat: u8,
+}
+
+/// Values of the last reported readings. Holds up to `N` numeric values,
+/// readings with more are always reported.
+pub struct LastReport<const N: usize> {
+    signature: u32,
+    len: usize,
+    values: [f32; N],
+    wakes: u8,
+    overflow_warned: bool,
+}
+
+impl<const N: usize> LastReport<N> {
+    pub const fn new() -> Self {
+        Self {
+            signature: 0,
+            len: 0,
+            values: [0.0; N],
+            wakes: 0,
+            overflow_warned: false,
+        }
+    }
+
+    /// Count a wake-up and tell whether `readings` (`(endpoint, payload)`)
+    /// changed enough since the last report, or the heartbeat is due.
+    pub fn should_report(
+        &mut self,
+        readings: &[(&str, String)],
+        thresholds: &ReportThresholds,
+    ) -> bool {
+        self.wakes = self.wakes.saturating_add(1);
+
+        if thresholds.delta <= 0.0 || self.wakes >= thresholds.heartbeat {
+            return true;
+        }
+
+        let (signature, values) = numeric_values(readings);
+
+        if values.len() > N {
+            if !self.overflow_warned {
+                log::warn!(
+                    "{} values read but {} can be compared, every reading is reported",
+                    values.len(),
+                    N
+                );
+                self.overflow_warned = true;
+            }
+            return true;
+        }
+
+        // Sensors were added or removed, or nothing was reported yet
+        if signature != self.signature || values.len() != self.len {
+            return true;
+        }
+
+        values
+            .iter()
+            .zip(self.values.iter())
+            .any(|((key, value), last)| {
+                if *key == BATTERY_FIELD {
+                    last - value > thresholds.battery_delta
+                } else {
+                    (value - last).abs() > thresholds.delta
+                }
+            })
+    }
+
+    /// Remember `readings` as the last reported ones, once they are delivered.
+    pub fn record(&mut self, readings: &[(&str, String)]) {
+        let (signature, values) = numeric_values(readings);
+
+        self.wakes = 0;
+        self.signature = signature;
+        self.len = values.len().min(N);
+
+        for (slot, (_, value)) in self.values.iter_mut().zip(values) {
+            *slot = value;
+        }
+    }
+
+    pub fn wakes_since_report(&self) -> u8 {
+        self.wakes
+    }
+}
+
+impl<const N: usize> Default for LastReport<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Compared fields of every payload, in a stable order, and a hash of their
+/// endpoints and names.
+fn numeric_values(readings: &[(&str, String)]) -> (u32, Vec<(String, f32)>) {
+    // FNV-1a
+    let mut signature: u32 = 0x811c_9dc5;
+    let mut hash = |bytes: &[u8]| {
+        for byte in bytes {
+            signature ^= *byte as u32;
+            signature = signature.wrapping_mul(0x0100_0193);
+        }
+    };
+
+    let mut values = Vec::new();
+
+    for (endpoint, payload) in readings {
+        let map = match serde_json::from_str::<Value>(payload) {
+            Ok(Value::Object(map)) => map,
+            _ => continue,
+        };
+
+        hash(endpoint.as_bytes());
+
+        for (key, value) in map {
+            if key != BATTERY_FIELD && !DELTA_FIELDS.contains(&key.as_str()) {
+                continue;
+            }
+
+            if let Some(value) = value.as_f64() {
+                hash(key.as_bytes());
+                values.push((key, value as f32));
+            }
+        }
+    }
+
+    (signature, values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: ReportThresholds = ReportThresholds {
+        delta: 1.0,
+        battery_delta: 5.0,
+        heartbeat: 10,
+    };
+
+    fn readings(level: f32, battery: f32) -> Vec<(&'static str, String)> {
+        vec![(
+            "send_soil_moisture",
+            format!(r#"{{"id":1,"battery":{},"level":{}}}"#, battery, level),
+        )]
+    }
+
+    #[test]
+    fn reports_significant_changes() {
+        let mut last = LastReport::<4>::new();
+
+        assert!(last.should_report(&readings(40.0, 80.0), &THRESHOLDS));
+        last.record(&readings(40.0, 80.0));
+
+        assert!(!last.should_report(&readings(40.5, 80.0), &THRESHOLDS));
+        assert!(!last.should_report(&readings(40.0, 81.0), &THRESHOLDS));
+        assert!(last.should_report(&readings(42.0, 80.0), &THRESHOLDS));
+        assert!(last.should_report(&readings(40.0, 70.0), &THRESHOLDS));
+        assert_eq!(last.wakes_since_report(), 4);
+    }
+
+    #[test]
+    fn distance_jitter_not_reported() {
+        let water = |level: f32, measure: f32| {
+            vec![(
+                "send_water_level",
+                format!(
+                    r#"{{"id":1,"battery":80.0,"level":{},"measure":{}}}"#,
+                    level, measure
+                ),
+            )]
+        };
+        let mut last = LastReport::<4>::new();
+        last.record(&water(50.0, 520.0));
+
+        assert!(!last.should_report(&water(50.0, 523.0), &THRESHOLDS));
+        assert!(!last.should_report(&water(50.5, 515.0), &THRESHOLDS));
+        assert!(last.should_report(&water(52.0, 500.0), &THRESHOLDS));
+    }
+
+    #[test]
+    fn unrecorded_report_is_compared_again() {
+        let mut last = LastReport::<4>::new();
+        last.record(&readings(40.0, 80.0));
+
+        // Not delivered, so not recorded
+        assert!(last.should_report(&readings(42.0, 80.0), &THRESHOLDS));
+        assert!(last.should_report(&readings(41.5, 80.0), &THRESHOLDS));
+    }
+
+    #[test]
+    fn heartbeat_and_overflow_always_report() {
+        let mut last = LastReport::<1>::new();
+        last.record(&readings(40.0, 80.0));
+
+        assert!(last.should_report(&readings(40.0, 80.0), &THRESHOLDS));
+        assert!(last.overflow_warned);
+
+        let mut last = LastReport::<4>::new();
+        last.record(&readings(40.0, 80.0));
+        for _ in 1..THRESHOLDS.heartbeat {
+            assert!(!last.should_report(&readings(40.0, 80.0), &THRESHOLDS));
+        }
+        assert!(last.should_report(&readings(40.0, 80.0), &THRESHOLDS));
+    }
+}
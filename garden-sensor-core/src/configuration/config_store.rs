@@ -0,0 +1,99 @@
+/// Typed key-value storage backing [`NvsConfiguration`](super::nvs_configuration::NvsConfiguration).
+///
+/// Getters return `None` when the key is missing or holds another type.
+pub trait ConfigStore {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    fn get_str(&self, key: &str) -> Option<String>;
+    fn set_str(&mut self, key: &str, value: &str) -> Result<(), Self::Error>;
+
+    fn get_u8(&self, key: &str) -> Option<u8>;
+    fn set_u8(&mut self, key: &str, value: u8) -> Result<(), Self::Error>;
+
+    fn get_u32(&self, key: &str) -> Option<u32>;
+    fn set_u32(&mut self, key: &str, value: u32) -> Result<(), Self::Error>;
+
+    fn get_u64(&self, key: &str) -> Option<u64>;
+    fn set_u64(&mut self, key: &str, value: u64) -> Result<(), Self::Error>;
+
+    fn get_f32(&self, key: &str) -> Option<f32>;
+    fn set_f32(&mut self, key: &str, value: f32) -> Result<(), Self::Error>;
+
+    fn get_blob(&self, key: &str) -> Option<Vec<u8>>;
+    fn set_blob(&mut self, key: &str, value: &[u8]) -> Result<(), Self::Error>;
+
+    /// Remove `key`, removing a missing key is not an error.
+    fn remove(&mut self, key: &str) -> Result<(), Self::Error>;
+}
+
+/// Value of a key as held by a store, whatever its type
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreValue {
+    Str(String),
+    U8(u8),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    Blob(Vec<u8>),
+}
+
+/// Checks shared by the tests of every store.
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::ConfigStore;
+
+    /// Every typed value reads back as stored and is gone once removed.
+    pub fn round_trip<S: ConfigStore>(store: &mut S) {
+        store.set_str("str", "value").unwrap();
+        store.set_u8("u8", 200).unwrap();
+        store.set_u32("u32", 0xdead_beef).unwrap();
+        store.set_u64("u64", 86_400_000_000).unwrap();
+        store.set_f32("f32", -1.25).unwrap();
+        store.set_blob("blob", &[0, 1, 255]).unwrap();
+
+        assert_eq!(store.get_str("str").as_deref(), Some("value"));
+        assert_eq!(store.get_u8("u8"), Some(200));
+        assert_eq!(store.get_u32("u32"), Some(0xdead_beef));
+        assert_eq!(store.get_u64("u64"), Some(86_400_000_000));
+        assert_eq!(store.get_f32("f32"), Some(-1.25));
+        assert_eq!(store.get_blob("blob"), Some(vec![0, 1, 255]));
+
+        store.set_u8("u8", 7).unwrap();
+        assert_eq!(store.get_u8("u8"), Some(7));
+
+        for key in ["str", "u8", "u32", "u64", "f32", "blob"] {
+            store.remove(key).unwrap();
+        }
+
+        assert_eq!(store.get_str("str"), None);
+        assert_eq!(store.get_u8("u8"), None);
+        assert_eq!(store.get_u32("u32"), None);
+        assert_eq!(store.get_u64("u64"), None);
+        assert_eq!(store.get_f32("f32"), None);
+        assert_eq!(store.get_blob("blob"), None);
+
+        // Removing a missing key is not an error
+        store.remove("missing").unwrap();
+    }
+
+    /// A value is only read back by the getter of its own type.
+    pub fn type_isolation<S: ConfigStore>(store: &mut S) {
+        store.set_u8("key", 1).unwrap();
+
+        assert_eq!(store.get_u8("key"), Some(1));
+        assert_eq!(store.get_u32("key"), None);
+        assert_eq!(store.get_u64("key"), None);
+        assert_eq!(store.get_f32("key"), None);
+        assert_eq!(store.get_str("key"), None);
+        assert_eq!(store.get_blob("key"), None);
+
+        store.set_u32("key", 1).unwrap();
+        assert_eq!(store.get_u8("key"), None);
+        assert_eq!(store.get_u32("key"), Some(1));
+
+        store.set_str("key", "1").unwrap();
+        assert_eq!(store.get_u32("key"), None);
+        assert_eq!(store.get_blob("key"), None);
+        assert_eq!(store.get_str("key").as_deref(), Some("1"));
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Map, Value};
+
+use super::config_store::ConfigStore;
+
+/// [`ConfigStore`] persisted as a JSON document, for host tools and
+/// simulators. Every write rewrites the whole file.
+///
+/// Values are tagged with their type (`{"SLEEP": {"u64": 600000000}}`) so
+/// typed getters behave like NVS and do not mix `u8` and `u32` keys.
+#[derive(Debug)]
+pub struct FileConfigStore {
+    path: PathBuf,
+    values: Map<String, Value>,
+}
+
+impl FileConfigStore {
+    /// Open `path`, a missing file is an empty store.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let values = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Map::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self { path, values })
+    }
+
+    fn get(&self, key: &str, type_tag: &str) -> Option<&Value> {
+        self.values.get(key)?.get(type_tag)
+    }
+
+    fn set(&mut self, key: &str, type_tag: &str, value: Value) -> io::Result<()> {
+        let mut tagged = Map::new();
+        tagged.insert(type_tag.to_string(), value);
+
+        self.values.insert(key.to_string(), Value::Object(tagged));
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(&self.values)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        fs::write(&self.path, content)
+    }
+}
+
+impl ConfigStore for FileConfigStore {
+    type Error = io::Error;
+
+    fn get_str(&self, key: &str) -> Option<String> {
+        self.get(key, "str")?.as_str().map(str::to_string)
+    }
+
+    fn set_str(&mut self, key: &str, value: &str) -> Result<(), Self::Error> {
+        self.set(key, "str", json!(value))
+    }
+
+    fn get_u8(&self, key: &str) -> Option<u8> {
+        self.get(key, "u8")?.as_u64()?.try_into().ok()
+    }
+
+    fn set_u8(&mut self, key: &str, value: u8) -> Result<(), Self::Error> {
+        self.set(key, "u8", json!(value))
+    }
+
+    fn get_u32(&self, key: &str) -> Option<u32> {
+        self.get(key, "u32")?.as_u64()?.try_into().ok()
+    }
+
+    fn set_u32(&mut self, key: &str, value: u32) -> Result<(), Self::Error> {
+        self.set(key, "u32", json!(value))
+    }
+
+    fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key, "u64")?.as_u64()
+    }
+
+    fn set_u64(&mut self, key: &str, value: u64) -> Result<(), Self::Error> {
+        self.set(key, "u64", json!(value))
+    }
+
+    fn get_f32(&self, key: &str) -> Option<f32> {
+        self.get(key, "f32")?.as_f64().map(|v| v as f32)
+    }
+
+    fn set_f32(&mut self, key: &str, value: f32) -> Result<(), Self::Error> {
+        self.set(key, "f32", json!(value))
+    }
+
+    fn get_blob(&self, key: &str) -> Option<Vec<u8>> {
+        self.get(key, "blob")?
+            .as_array()?
+            .iter()
+            .map(|b| b.as_u64()?.try_into().ok())
+            .collect()
+    }
+
+    fn set_blob(&mut self, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+        self.set(key, "blob", json!(value))
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Self::Error> {
+        if self.values.remove(key).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::config_store::tests::{round_trip, type_isolation};
+
+    /// Path of a file removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "garden-sensor-{}-{}.json",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn typed_round_trip() {
+        let file = TempFile::new("round-trip");
+        round_trip(&mut FileConfigStore::open(&file.0).unwrap());
+    }
+
+    #[test]
+    fn typed_keys_isolated() {
+        let file = TempFile::new("isolation");
+        type_isolation(&mut FileConfigStore::open(&file.0).unwrap());
+    }
+
+    #[test]
+    fn values_persisted() {
+        let file = TempFile::new("persisted");
+
+        let mut store = FileConfigStore::open(&file.0).unwrap();
+        store.set_u8("u8", 3).unwrap();
+        store.set_f32("f32", 0.5).unwrap();
+        store.set_blob("blob", b"cert\0").unwrap();
+        store.set_str("str", "value").unwrap();
+        store.set_str("gone", "value").unwrap();
+        store.remove("gone").unwrap();
+
+        let store = FileConfigStore::open(&file.0).unwrap();
+        assert_eq!(store.get_u8("u8"), Some(3));
+        assert_eq!(store.get_u32("u8"), None);
+        assert_eq!(store.get_f32("f32"), Some(0.5));
+        assert_eq!(store.get_blob("blob"), Some(b"cert\0".to_vec()));
+        assert_eq!(store.get_str("str").as_deref(), Some("value"));
+        assert_eq!(store.get_str("gone"), None);
+    }
+
+    #[test]
+    fn invalid_file_rejected() {
+        let file = TempFile::new("invalid");
+        fs::write(&file.0, "not json").unwrap();
+
+        let error = FileConfigStore::open(&file.0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
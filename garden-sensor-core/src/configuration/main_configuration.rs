@@ -0,0 +1,1181 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use serde_json::{Map, Value};
+use url_encoded_data::UrlEncodedData;
+
+use super::config_store::ConfigStore;
+use super::nvs_configuration::*;
+use crate::payload::fill_placeholders;
+use crate::string_error::StringError;
+
+#[cfg(not(any(
+    feature = "moisture-sensor",
+    feature = "water-level-sensor",
+    feature = "aht10-sensor"
+)))]
+compile_error!("At least one sensor feature must be enabled!");
+
+#[derive(Debug)]
+pub enum MapFormType {
+    String(&'static str, usize),
+    /// String never shown back, left unchanged when submitted empty
+    Secret(usize),
+    /// IPv4 address as a string, empty when not set
+    Ipv4,
+    Float(f32),
+    U32Hex(u32),
+    Unsigned32(u32),
+    Unsigned64(u64),
+    Unsigned8(u8),
+}
+
+#[derive(Debug)]
+pub struct MapFormElement {
+    pub nvs_key: &'static str,
+    pub form_name: &'static str,
+    pub template_id: Option<&'static str>,
+    pub data_type: MapFormType,
+    /// Accepted values of a numeric field or lengths of a string, bounds
+    /// included
+    pub range: Option<RangeInclusive<f64>>,
+}
+
+impl MapFormElement {
+    pub fn is_secret(&self) -> bool {
+        matches!(self.data_type, MapFormType::Secret(_))
+    }
+
+    /// `true` when `data` leaves the stored value as is: an empty secret.
+    pub fn keeps_value(&self, data: &str) -> bool {
+        self.is_secret() && data.is_empty()
+    }
+}
+
+/// Longest IPv4 address string, `255.255.255.255`
+const MAX_IPV4_LEN: usize = 15;
+
+/// GPIO numbers of the ESP32-C3
+const GPIO_RANGE: RangeInclusive<f64> = 0.0..=21.0;
+
+/// ADC1 GPIOs free for the moisture probe: 2 and 4, GPIO 3 measures the
+/// battery and is refused when the sensors are built
+#[cfg(feature = "moisture-sensor")]
+const MOISTURE_ADC_RANGE: RangeInclusive<f64> = 2.0..=4.0;
+
+pub const MAP_NVS_FORM: &[MapFormElement] = &[
+    MapFormElement {
+        nvs_key: KEY_SSID,
+        form_name: "ssid",
+        template_id: Some("{SSID}"),
+        data_type: MapFormType::String("", 32),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_PASSPHRASE,
+        form_name: "pass",
+        template_id: None,
+        data_type: MapFormType::Secret(63),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_WIFI_AUTH,
+        form_name: "auth",
+        template_id: Some("{AUTH}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=5.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_SSID_2,
+        form_name: "ssid2",
+        template_id: Some("{SSID2}"),
+        data_type: MapFormType::String("", 32),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_PASSPHRASE_2,
+        form_name: "pass2",
+        template_id: None,
+        data_type: MapFormType::Secret(63),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_WIFI_AUTH_2,
+        form_name: "auth2",
+        template_id: Some("{AUTH2}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=5.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_SSID_3,
+        form_name: "ssid3",
+        template_id: Some("{SSID3}"),
+        data_type: MapFormType::String("", 32),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_PASSPHRASE_3,
+        form_name: "pass3",
+        template_id: None,
+        data_type: MapFormType::Secret(63),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_WIFI_AUTH_3,
+        form_name: "auth3",
+        template_id: Some("{AUTH3}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=5.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_AP_PASSWORD,
+        form_name: "ap_pass",
+        template_id: None,
+        data_type: MapFormType::Secret(63),
+        range: Some(8.0..=63.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_STATIC_IP,
+        form_name: "static_ip",
+        template_id: Some("{STATIC_IP}"),
+        data_type: MapFormType::Ipv4,
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_NETMASK,
+        form_name: "netmask",
+        template_id: Some("{NETMASK}"),
+        data_type: MapFormType::Ipv4,
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_GATEWAY,
+        form_name: "gateway",
+        template_id: Some("{GATEWAY}"),
+        data_type: MapFormType::Ipv4,
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_DNS,
+        form_name: "dns",
+        template_id: Some("{DNS}"),
+        data_type: MapFormType::Ipv4,
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_SERVER_ADDRESS,
+        form_name: "srvaddr",
+        template_id: Some("{SRVADDR}"),
+        data_type: MapFormType::String("192.168.70.1", 128),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_UPLINK,
+        form_name: "uplink",
+        template_id: Some("{UPLINK}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=1.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_HTTP_TLS,
+        form_name: "http_tls",
+        template_id: Some("{HTTP_TLS}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=2.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_URL_TEMPLATE,
+        form_name: "url_tpl",
+        template_id: Some("{URL_TPL}"),
+        data_type: MapFormType::String("", 128),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_HTTP_METHOD,
+        form_name: "http_method",
+        template_id: Some("{HTTP_METHOD}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=1.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_HTTP_HEADERS,
+        form_name: "http_headers",
+        template_id: None,
+        data_type: MapFormType::Secret(256),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_MQTT_HOST,
+        form_name: "mqtt_host",
+        template_id: Some("{MQTT_HOST}"),
+        data_type: MapFormType::String("", 128),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_MQTT_PORT,
+        form_name: "mqtt_port",
+        template_id: Some("{MQTT_PORT}"),
+        data_type: MapFormType::Unsigned32(1883),
+        range: Some(1.0..=65535.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_MQTT_USER,
+        form_name: "mqtt_user",
+        template_id: Some("{MQTT_USER}"),
+        data_type: MapFormType::String("", 64),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_MQTT_PASS,
+        form_name: "mqtt_pass",
+        template_id: None,
+        data_type: MapFormType::Secret(64),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_MQTT_TOPIC,
+        form_name: "mqtt_topic",
+        template_id: Some("{MQTT_TOPIC}"),
+        data_type: MapFormType::String("garden/{id}/{endpoint}", 128),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_MQTT_QOS,
+        form_name: "mqtt_qos",
+        template_id: Some("{MQTT_QOS}"),
+        data_type: MapFormType::Unsigned8(1),
+        range: Some(0.0..=2.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_MQTT_RETAIN,
+        form_name: "mqtt_retain",
+        template_id: Some("{MQTT_RETAIN}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=1.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_HA_DISCOVERY,
+        form_name: "ha_discovery",
+        template_id: Some("{HA_DISCOVERY}"),
+        data_type: MapFormType::Unsigned8(1),
+        range: Some(0.0..=1.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_NAME,
+        form_name: "name",
+        template_id: Some("{NAME}"),
+        data_type: MapFormType::String("", 32),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_ID,
+        form_name: "id",
+        template_id: Some("{ID}"),
+        data_type: MapFormType::U32Hex(0),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_SLEEP,
+        form_name: "sleep",
+        template_id: Some("{SLEEP}"),
+        data_type: MapFormType::Unsigned64(3_600_000_000),
+        range: Some(10_000_000.0..=86_400_000_000.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_BATCH_SIZE,
+        form_name: "batch",
+        template_id: Some("{BATCH}"),
+        data_type: MapFormType::Unsigned8(1),
+        range: Some(1.0..=16.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_REPORT_DELTA,
+        form_name: "report_delta",
+        template_id: Some("{REPORT_DELTA}"),
+        data_type: MapFormType::Float(0.0),
+        range: Some(0.0..=100.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_BATTERY_DELTA,
+        form_name: "battery_delta",
+        template_id: Some("{BATTERY_DELTA}"),
+        data_type: MapFormType::Float(5.0),
+        range: Some(0.0..=100.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_HEARTBEAT,
+        form_name: "heartbeat",
+        template_id: Some("{HEARTBEAT}"),
+        data_type: MapFormType::Unsigned8(12),
+        range: Some(1.0..=255.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_NTP_SERVER,
+        form_name: "ntp_server",
+        template_id: Some("{NTP_SERVER}"),
+        data_type: MapFormType::String("pool.ntp.org", 64),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_NTP_RESYNC,
+        form_name: "ntp_resync",
+        template_id: Some("{NTP_RESYNC}"),
+        data_type: MapFormType::Unsigned8(24),
+        range: Some(1.0..=255.0),
+    },
+    MapFormElement {
+        nvs_key: KEY_OTA_URL,
+        form_name: "ota_url",
+        template_id: Some("{OTA_URL}"),
+        data_type: MapFormType::String("", 128),
+        range: None,
+    },
+    MapFormElement {
+        nvs_key: KEY_TX_POWER,
+        form_name: "txpwr",
+        template_id: Some("{TXPWR}"),
+        data_type: MapFormType::Unsigned8(80),
+        range: Some(8.0..=80.0),
+    },
+    #[cfg(feature = "moisture-sensor")]
+    MapFormElement {
+        nvs_key: KEY_MOIST_ENABLE,
+        form_name: "moist_en",
+        template_id: Some("{MOIST_EN}"),
+        data_type: MapFormType::Unsigned8(1),
+        range: Some(0.0..=1.0),
+    },
+    #[cfg(feature = "moisture-sensor")]
+    MapFormElement {
+        nvs_key: KEY_MOIST_PIN_ADC,
+        form_name: "moist_pin_adc",
+        template_id: Some("{MOIST_PIN_ADC}"),
+        data_type: MapFormType::Unsigned8(4),
+        range: Some(MOISTURE_ADC_RANGE),
+    },
+    #[cfg(feature = "moisture-sensor")]
+    MapFormElement {
+        nvs_key: KEY_MOIST_PIN_EN,
+        form_name: "moist_pin_en",
+        template_id: Some("{MOIST_PIN_EN}"),
+        data_type: MapFormType::Unsigned8(6),
+        range: Some(GPIO_RANGE),
+    },
+    #[cfg(feature = "moisture-sensor")]
+    MapFormElement {
+        nvs_key: KEY_MOIST_VHIGH,
+        form_name: "vhigh_moist",
+        template_id: Some("{VHIGH_MOIST}"),
+        data_type: MapFormType::Float(DEFAULT_MOIST_VHIGH),
+        range: Some(0.0..=3.3),
+    },
+    #[cfg(feature = "moisture-sensor")]
+    MapFormElement {
+        nvs_key: KEY_MOIST_VLOW,
+        form_name: "vlow_moist",
+        template_id: Some("{VLOW_MOIST}"),
+        data_type: MapFormType::Float(DEFAULT_MOIST_VLOW),
+        range: Some(0.0..=3.3),
+    },
+    #[cfg(feature = "water-level-sensor")]
+    MapFormElement {
+        nvs_key: KEY_WATER_ENABLE,
+        form_name: "water_en",
+        template_id: Some("{WATER_EN}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=1.0),
+    },
+    #[cfg(feature = "water-level-sensor")]
+    MapFormElement {
+        nvs_key: KEY_WATER_PIN_EN,
+        form_name: "water_pin_en",
+        template_id: Some("{WATER_PIN_EN}"),
+        data_type: MapFormType::Unsigned8(10),
+        range: Some(GPIO_RANGE),
+    },
+    #[cfg(feature = "water-level-sensor")]
+    MapFormElement {
+        nvs_key: KEY_WATER_PIN_TRIG,
+        form_name: "water_pin_trig",
+        template_id: Some("{WATER_PIN_TRIG}"),
+        data_type: MapFormType::Unsigned8(2),
+        range: Some(GPIO_RANGE),
+    },
+    #[cfg(feature = "water-level-sensor")]
+    MapFormElement {
+        nvs_key: KEY_WATER_PIN_ECHO,
+        form_name: "water_pin_echo",
+        template_id: Some("{WATER_PIN_ECHO}"),
+        data_type: MapFormType::Unsigned8(5),
+        range: Some(GPIO_RANGE),
+    },
+    #[cfg(feature = "water-level-sensor")]
+    MapFormElement {
+        nvs_key: KEY_WATER_HIGH,
+        form_name: "water_high",
+        template_id: Some("{WATER_HIGH}"),
+        data_type: MapFormType::Float(DEFAULT_WATER_HIGH),
+        range: Some(0.0..=3000.0),
+    },
+    #[cfg(feature = "water-level-sensor")]
+    MapFormElement {
+        nvs_key: KEY_WATER_LOW,
+        form_name: "water_low",
+        template_id: Some("{WATER_LOW}"),
+        data_type: MapFormType::Float(DEFAULT_WATER_LOW),
+        range: Some(0.0..=3000.0),
+    },
+    #[cfg(feature = "aht10-sensor")]
+    MapFormElement {
+        nvs_key: KEY_AHT_ENABLE,
+        form_name: "aht_en",
+        template_id: Some("{AHT_EN}"),
+        data_type: MapFormType::Unsigned8(0),
+        range: Some(0.0..=1.0),
+    },
+    #[cfg(feature = "aht10-sensor")]
+    MapFormElement {
+        nvs_key: KEY_AHT_PIN_SDA,
+        form_name: "aht_pin_sda",
+        template_id: Some("{AHT_PIN_SDA}"),
+        data_type: MapFormType::Unsigned8(8),
+        range: Some(GPIO_RANGE),
+    },
+    #[cfg(feature = "aht10-sensor")]
+    MapFormElement {
+        nvs_key: KEY_AHT_PIN_SCL,
+        form_name: "aht_pin_scl",
+        template_id: Some("{AHT_PIN_SCL}"),
+        data_type: MapFormType::Unsigned8(9),
+        range: Some(GPIO_RANGE),
+    },
+    #[cfg(feature = "aht10-sensor")]
+    MapFormElement {
+        nvs_key: KEY_AHT_VARIANT,
+        form_name: "aht_variant",
+        template_id: Some("{AHT_VARIANT}"),
+        data_type: MapFormType::Unsigned8(10),
+        range: Some(10.0..=20.0),
+    },
+    #[cfg(feature = "aht10-sensor")]
+    MapFormElement {
+        nvs_key: KEY_AHT_TEMP_OFFSET,
+        form_name: "temp_offset",
+        template_id: Some("{TEMP_OFFSET}"),
+        data_type: MapFormType::Float(0.0),
+        range: Some(-20.0..=20.0),
+    },
+    #[cfg(feature = "aht10-sensor")]
+    MapFormElement {
+        nvs_key: KEY_AHT_HUM_OFFSET,
+        form_name: "hum_offset",
+        template_id: Some("{HUM_OFFSET}"),
+        data_type: MapFormType::Float(0.0),
+        range: Some(-20.0..=20.0),
+    },
+];
+
+/// A typed settings value, with the same variants as [`MapFormType`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapFormValue {
+    String(String),
+    Float(f32),
+    U32Hex(u32),
+    Unsigned32(u32),
+    Unsigned64(u64),
+    Unsigned8(u8),
+}
+
+impl MapFormValue {
+    /// Parse a form field according to the element data type.
+    pub fn parse(data_type: &MapFormType, data: &str) -> Result<Self, StringError> {
+        let number = data.trim();
+
+        match data_type {
+            MapFormType::String(_, max_size) | MapFormType::Secret(max_size)
+                if data.len() > *max_size =>
+            {
+                Err(StringError("Value too long"))
+            }
+            MapFormType::String(_, _) | MapFormType::Secret(_) => {
+                Ok(MapFormValue::String(data.to_string()))
+            }
+            MapFormType::Ipv4 if number.is_empty() => Ok(MapFormValue::String(String::new())),
+            MapFormType::Ipv4 => Ipv4Addr::from_str(number)
+                .map(|ip| MapFormValue::String(ip.to_string()))
+                .map_err(|_| StringError("Invalid IPv4 address")),
+
+            MapFormType::Float(_) => f32::from_str(number)
+                .map(MapFormValue::Float)
+                .map_err(|_| StringError("Invalid decimal value")),
+
+            MapFormType::U32Hex(_) => u32::from_str_radix(number, 16)
+                .map(MapFormValue::U32Hex)
+                .map_err(|_| StringError("Invalid hexadecimal value")),
+
+            MapFormType::Unsigned32(_) => u32::from_str(number)
+                .map(MapFormValue::Unsigned32)
+                .map_err(|_| StringError("Invalid integer value")),
+
+            MapFormType::Unsigned64(_) => u64::from_str(number)
+                .map(MapFormValue::Unsigned64)
+                .map_err(|_| StringError("Invalid integer value")),
+
+            MapFormType::Unsigned8(_) => u8::from_str(number)
+                .map(MapFormValue::Unsigned8)
+                .map_err(|_| StringError("Invalid integer value")),
+        }
+    }
+
+    /// Parse a form field and check it (or its length for strings) against the
+    /// element range.
+    pub fn parse_element(elem: &MapFormElement, data: &str) -> Result<Self, StringError> {
+        let value = Self::parse(&elem.data_type, data)?;
+
+        let number = match value {
+            MapFormValue::String(ref s) => s.len() as f64,
+            MapFormValue::Float(v) if !v.is_finite() => {
+                return Err(StringError("Invalid decimal value"))
+            }
+            MapFormValue::Float(v) => v as f64,
+            MapFormValue::U32Hex(v) | MapFormValue::Unsigned32(v) => v as f64,
+            MapFormValue::Unsigned64(v) => v as f64,
+            MapFormValue::Unsigned8(v) => v as f64,
+        };
+
+        match &elem.range {
+            Some(range) if !range.contains(&number) => match value {
+                MapFormValue::String(_) => Err(StringError("Length out of range")),
+                _ => Err(StringError("Value out of range")),
+            },
+            _ => Ok(value),
+        }
+    }
+}
+
+/// Settings field rejected by the validation
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: &'static str,
+}
+
+/// Every invalid field of a settings form, nothing was stored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormErrors(pub Vec<FieldError>);
+
+impl fmt::Display for FormErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}: {}", error.field, error.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for FormErrors {}
+
+/// Format the value the way the settings form expects it.
+impl fmt::Display for MapFormValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapFormValue::String(s) => f.write_str(s),
+            MapFormValue::Float(v) => write!(f, "{}", v),
+            MapFormValue::U32Hex(v) => write!(f, "{:x}", v),
+            MapFormValue::Unsigned32(v) => write!(f, "{}", v),
+            MapFormValue::Unsigned64(v) => write!(f, "{}", v),
+            MapFormValue::Unsigned8(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// Current value of a settings element, or its default if not stored yet.
+pub fn read_form_value<S: ConfigStore>(
+    config: &NvsConfiguration<S>,
+    elem: &MapFormElement,
+) -> MapFormValue {
+    match elem.data_type {
+        MapFormType::String(default, _) => {
+            MapFormValue::String(config.read_string(elem.nvs_key, default))
+        }
+        MapFormType::Secret(_) | MapFormType::Ipv4 => {
+            MapFormValue::String(config.read_string(elem.nvs_key, ""))
+        }
+        MapFormType::Float(default) => {
+            MapFormValue::Float(config.read_float(elem.nvs_key, default))
+        }
+        MapFormType::U32Hex(default) => {
+            MapFormValue::U32Hex(config.read_u32(elem.nvs_key, default))
+        }
+        MapFormType::Unsigned32(default) => {
+            MapFormValue::Unsigned32(config.read_u32(elem.nvs_key, default))
+        }
+        MapFormType::Unsigned64(default) => {
+            MapFormValue::Unsigned64(config.read_u64(elem.nvs_key, default))
+        }
+        MapFormType::Unsigned8(default) => {
+            MapFormValue::Unsigned8(config.read_u8(elem.nvs_key, default))
+        }
+    }
+}
+
+pub fn store_form_value<S: ConfigStore>(
+    config: &mut NvsConfiguration<S>,
+    elem: &MapFormElement,
+    value: &MapFormValue,
+) -> Result<(), S::Error> {
+    match value {
+        MapFormValue::String(s) => {
+            let max_size = match elem.data_type {
+                MapFormType::String(_, max_size) | MapFormType::Secret(max_size) => max_size,
+                MapFormType::Ipv4 => MAX_IPV4_LEN,
+                _ => s.len(),
+            };
+            config.store_string(elem.nvs_key, s, max_size)
+        }
+        MapFormValue::Float(v) => config.store_float(elem.nvs_key, *v),
+        MapFormValue::U32Hex(v) | MapFormValue::Unsigned32(v) => config.store_u32(elem.nvs_key, *v),
+        MapFormValue::Unsigned64(v) => config.store_u64(elem.nvs_key, *v),
+        MapFormValue::Unsigned8(v) => config.store_u8(elem.nvs_key, *v),
+    }
+}
+
+/// URL of `endpoint`: the URL template with its placeholders filled, or
+/// `<scheme>://<server address>/<endpoint>` when no template is set.
+pub fn make_http_url<S: ConfigStore>(config: &NvsConfiguration<S>, endpoint: &str) -> String {
+    let template = config.get_url_template();
+
+    if template.is_empty() {
+        let scheme = match config.get_http_tls() {
+            HttpTls::None => "http",
+            HttpTls::Bundle | HttpTls::CustomCa => "https",
+        };
+
+        return format!("{}://{}/{}", scheme, config.get_server_address(), endpoint);
+    }
+
+    make_url(config, &template, endpoint)
+}
+
+/// Fill the placeholders of an URL template, the name is percent-encoded.
+pub fn make_url<S: ConfigStore>(
+    config: &NvsConfiguration<S>,
+    template: &str,
+    endpoint: &str,
+) -> String {
+    fill_placeholders(
+        template,
+        config.get_id(),
+        &percent_encode(&config.get_name()),
+        endpoint,
+    )
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut result = String::new();
+
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result += &format!("%{:02X}", byte),
+        }
+    }
+
+    result
+}
+
+/// Parse an url-encoded settings form and store every field present in it.
+/// Nothing is stored if a field is invalid.
+pub fn apply_form<S: ConfigStore>(
+    config: &mut NvsConfiguration<S>,
+    post_str: &str,
+) -> anyhow::Result<()> {
+    store_values(config, &parse_form(post_str)?)?;
+
+    Ok(())
+}
+
+/// Apply the body of a settings form submission, returns the message shown
+/// on the settings page.
+pub fn apply_form_body<S: ConfigStore>(config: &mut NvsConfiguration<S>, body: &[u8]) -> String {
+    if body.is_empty() {
+        return "Save error: No body".to_string();
+    }
+
+    match std::str::from_utf8(body) {
+        Ok(post_str) => match apply_form(config, post_str) {
+            Ok(_) => "Save successfully!".to_string(),
+            Err(e) => format!("Save error: {}", e),
+        },
+        Err(_) => "Save error: Body is not UTF-8".to_string(),
+    }
+}
+
+/// Store every value. If the store fails, the keys already written are
+/// restored as they were stored, or removed if they were absent.
+fn store_values<S: ConfigStore>(
+    config: &mut NvsConfiguration<S>,
+    values: &[(&'static MapFormElement, MapFormValue)],
+) -> Result<(), S::Error> {
+    let previous: Vec<_> = values
+        .iter()
+        .map(|(elem, _)| config.snapshot(elem.nvs_key))
+        .collect();
+
+    for (i, (elem, value)) in values.iter().enumerate() {
+        if let Err(e) = store_form_value(config, elem, value) {
+            for ((elem, _), old) in values[..=i].iter().zip(&previous) {
+                let _ = config.restore(elem.nvs_key, old.as_ref());
+            }
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Settings the server may change in its responses: sleep, calibration,
+/// name, reporting and batching. Network, uplink and security settings stay
+/// under the control of the settings portal.
+pub const SERVER_SETTINGS: &[&str] = &[
+    "sleep",
+    #[cfg(feature = "moisture-sensor")]
+    "vhigh_moist",
+    #[cfg(feature = "moisture-sensor")]
+    "vlow_moist",
+    #[cfg(feature = "water-level-sensor")]
+    "water_high",
+    #[cfg(feature = "water-level-sensor")]
+    "water_low",
+    #[cfg(feature = "aht10-sensor")]
+    "temp_offset",
+    #[cfg(feature = "aht10-sensor")]
+    "hum_offset",
+    "name",
+    "report_delta",
+    "battery_delta",
+    "heartbeat",
+    "batch",
+];
+
+/// Apply the `config` object of a server response, keyed by settings form
+/// field names (e.g. `{"config": {"sleep": 600000000, "name": "Tomatoes"}}`).
+/// Fields outside [`SERVER_SETTINGS`] (e.g. the calibration of a sensor this
+/// firmware is built without) are ignored and logged. Returns the number of
+/// fields applied, 0 when the response has no `config` object.
+pub fn apply_json<S: ConfigStore>(
+    config: &mut NvsConfiguration<S>,
+    response: &str,
+) -> anyhow::Result<usize> {
+    let fields = match serde_json::from_str::<Value>(response) {
+        Ok(Value::Object(mut document)) => match document.remove("config") {
+            Some(Value::Object(fields)) => fields,
+            Some(_) => return Err(StringError("Server config is not a JSON object").into()),
+            None => return Ok(0),
+        },
+        _ => return Ok(0),
+    };
+
+    let (allowed, rejected): (Map<String, Value>, Map<String, Value>) = fields
+        .into_iter()
+        .partition(|(name, _)| SERVER_SETTINGS.contains(&name.as_str()));
+
+    if !rejected.is_empty() {
+        let names: Vec<&str> = rejected.keys().map(String::as_str).collect();
+        log::warn!(
+            "Server config fields not allowed or not in this firmware, ignored: {}",
+            names.join(", ")
+        );
+    }
+
+    apply_json_fields(config, &allowed)
+}
+
+/// Apply JSON fields keyed by settings form field names. Every field is
+/// checked first, nothing is stored if one is unknown or invalid. Returns the
+/// number of fields applied.
+pub fn apply_json_fields<S: ConfigStore>(
+    config: &mut NvsConfiguration<S>,
+    fields: &Map<String, Value>,
+) -> anyhow::Result<usize> {
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+
+    for (name, value) in fields {
+        let Some(elem) = MAP_NVS_FORM.iter().find(|e| e.form_name == name) else {
+            errors.push(FieldError {
+                field: name.clone(),
+                message: "Unknown settings field",
+            });
+            continue;
+        };
+
+        let parsed = match value {
+            Value::String(s) if elem.keeps_value(s) => continue,
+            Value::String(s) => MapFormValue::parse_element(elem, s),
+            Value::Number(n) => MapFormValue::parse_element(elem, &n.to_string()),
+            Value::Bool(b) => MapFormValue::parse_element(elem, &(*b as u8).to_string()),
+            _ => Err(StringError("Invalid settings value type")),
+        };
+
+        match parsed {
+            Ok(value) => values.push((elem, value)),
+            Err(e) => errors.push(FieldError {
+                field: name.clone(),
+                message: e.0,
+            }),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(FormErrors(errors).into());
+    }
+
+    store_values(config, &values)?;
+
+    Ok(values.len())
+}
+
+/// Map an url-encoded settings form to typed values, fields absent from the
+/// form are left out. Every invalid field is reported.
+pub fn parse_form(
+    post_str: &str,
+) -> Result<Vec<(&'static MapFormElement, MapFormValue)>, FormErrors> {
+    let post_data = UrlEncodedData::parse_str(post_str);
+    let mut result = Vec::new();
+    let mut errors = Vec::new();
+
+    for elem in MAP_NVS_FORM {
+        if let Some(data) = post_data.get_first(elem.form_name) {
+            if elem.keeps_value(data) {
+                continue;
+            }
+
+            match MapFormValue::parse_element(elem, data) {
+                Ok(value) => result.push((elem, value)),
+                Err(e) => errors.push(FieldError {
+                    field: elem.form_name.to_string(),
+                    message: e.0,
+                }),
+            }
+        }
+    }
+
+    // A static address without its netmask or gateway would fall back to DHCP
+    let is_set = |form_name| {
+        result.iter().any(|(elem, value)| {
+            elem.form_name == form_name && *value != MapFormValue::String(String::new())
+        })
+    };
+    if is_set("static_ip") {
+        for form_name in ["netmask", "gateway"] {
+            if !is_set(form_name) {
+                errors.push(FieldError {
+                    field: form_name.to_string(),
+                    message: "Required with a static IP address",
+                });
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(result)
+    } else {
+        Err(FormErrors(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::config_store::StoreValue;
+    use crate::configuration::memory_store::MemoryConfigStore;
+    use crate::configuration::nvs_configuration::mock::config;
+
+    /// Store refusing to write one key
+    struct FailingStore {
+        inner: MemoryConfigStore,
+        failing_key: &'static str,
+    }
+
+    impl FailingStore {
+        fn check(&self, key: &str) -> Result<(), StringError> {
+            if key == self.failing_key {
+                Err(StringError("Write failed"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl ConfigStore for FailingStore {
+        type Error = StringError;
+
+        fn get_str(&self, key: &str) -> Option<String> {
+            self.inner.get_str(key)
+        }
+        fn set_str(&mut self, key: &str, value: &str) -> Result<(), Self::Error> {
+            self.check(key)?;
+            self.inner.set_str(key, value).map_err(|e| match e {})
+        }
+        fn get_u8(&self, key: &str) -> Option<u8> {
+            self.inner.get_u8(key)
+        }
+        fn set_u8(&mut self, key: &str, value: u8) -> Result<(), Self::Error> {
+            self.check(key)?;
+            self.inner.set_u8(key, value).map_err(|e| match e {})
+        }
+        fn get_u32(&self, key: &str) -> Option<u32> {
+            self.inner.get_u32(key)
+        }
+        fn set_u32(&mut self, key: &str, value: u32) -> Result<(), Self::Error> {
+            self.check(key)?;
+            self.inner.set_u32(key, value).map_err(|e| match e {})
+        }
+        fn get_u64(&self, key: &str) -> Option<u64> {
+            self.inner.get_u64(key)
+        }
+        fn set_u64(&mut self, key: &str, value: u64) -> Result<(), Self::Error> {
+            self.check(key)?;
+            self.inner.set_u64(key, value).map_err(|e| match e {})
+        }
+        fn get_f32(&self, key: &str) -> Option<f32> {
+            self.inner.get_f32(key)
+        }
+        fn set_f32(&mut self, key: &str, value: f32) -> Result<(), Self::Error> {
+            self.check(key)?;
+            self.inner.set_f32(key, value).map_err(|e| match e {})
+        }
+        fn get_blob(&self, key: &str) -> Option<Vec<u8>> {
+            self.inner.get_blob(key)
+        }
+        fn set_blob(&mut self, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+            self.check(key)?;
+            self.inner.set_blob(key, value).map_err(|e| match e {})
+        }
+        fn remove(&mut self, key: &str) -> Result<(), Self::Error> {
+            self.inner.remove(key).map_err(|e| match e {})
+        }
+    }
+
+    fn field<'a>(
+        values: &'a [(&'static MapFormElement, MapFormValue)],
+        form_name: &str,
+    ) -> Option<&'a MapFormValue> {
+        values
+            .iter()
+            .find(|(elem, _)| elem.form_name == form_name)
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn parse_typed_fields() {
+        let values =
+            parse_form("ssid=My%20Wifi&id=ab12&sleep=600000000&report_delta=2.5&txpwr=%2040")
+                .unwrap();
+
+        assert_eq!(values.len(), 5);
+        assert_eq!(
+            field(&values, "ssid"),
+            Some(&MapFormValue::String("My Wifi".to_string()))
+        );
+        assert_eq!(field(&values, "id"), Some(&MapFormValue::U32Hex(0xab12)));
+        assert_eq!(
+            field(&values, "sleep"),
+            Some(&MapFormValue::Unsigned64(600_000_000))
+        );
+        assert_eq!(
+            field(&values, "report_delta"),
+            Some(&MapFormValue::Float(2.5))
+        );
+        assert_eq!(field(&values, "txpwr"), Some(&MapFormValue::Unsigned8(40)));
+    }
+
+    #[test]
+    fn parse_skips_unknown_fields_and_empty_secrets() {
+        let values = parse_form("pass=&mqtt_pass=secret&unknown=1").unwrap();
+
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].0.nvs_key, KEY_MQTT_PASS);
+    }
+
+    #[test]
+    fn parse_reports_every_invalid_field() {
+        let errors = parse_form("sleep=5&txpwr=high&id=xyz&static_ip=1.2.3&ssid=ok").unwrap_err();
+
+        assert_eq!(
+            errors.to_string(),
+            "static_ip: Invalid IPv4 address, id: Invalid hexadecimal value, \
+             sleep: Value out of range, txpwr: Invalid integer value"
+        );
+    }
+
+    #[test]
+    fn parse_checks_string_lengths() {
+        let ssid = "s".repeat(33);
+        let errors = parse_form(&format!("ssid={}&ap_pass=short", ssid)).unwrap_err();
+
+        assert_eq!(
+            errors.to_string(),
+            "ssid: Value too long, ap_pass: Length out of range"
+        );
+    }
+
+    #[test]
+    fn apply_stores_fields() {
+        let mut config = config();
+
+        apply_form(&mut config, "name=Tomatoes&sleep=600000000&pass=secret").unwrap();
+
+        assert_eq!(config.get_name(), "Tomatoes");
+        assert_eq!(config.get_deep_sleep_duration(), 600_000_000);
+        assert_eq!(config.get_passphrase(), "secret");
+
+        // An empty secret keeps the stored one
+        apply_form(&mut config, "name=Peppers&pass=").unwrap();
+        assert_eq!(config.get_name(), "Peppers");
+        assert_eq!(config.get_passphrase(), "secret");
+    }
+
+    #[test]
+    fn apply_nothing_when_invalid() {
+        let mut config = config();
+
+        assert!(apply_form(&mut config, "name=Tomatoes&sleep=0").is_err());
+        assert_eq!(config.get_name(), "");
+        assert_eq!(config.get_deep_sleep_duration(), 3_600_000_000);
+    }
+
+    #[test]
+    fn read_defaults() {
+        let config = config();
+
+        for elem in MAP_NVS_FORM {
+            let expected = match elem.data_type {
+                MapFormType::String(default, _) => MapFormValue::String(default.to_string()),
+                MapFormType::Secret(_) | MapFormType::Ipv4 => MapFormValue::String(String::new()),
+                MapFormType::Float(default) => MapFormValue::Float(default),
+                MapFormType::U32Hex(default) => MapFormValue::U32Hex(default),
+                MapFormType::Unsigned32(default) => MapFormValue::Unsigned32(default),
+                MapFormType::Unsigned64(default) => MapFormValue::Unsigned64(default),
+                MapFormType::Unsigned8(default) => MapFormValue::Unsigned8(default),
+            };
+
+            assert_eq!(
+                read_form_value(&config, elem),
+                expected,
+                "{}",
+                elem.form_name
+            );
+        }
+    }
+
+    #[test]
+    fn url_of_endpoint() {
+        let mut config = config();
+        apply_form(&mut config, "srvaddr=example.com&id=ab12&name=My%20garden").unwrap();
+        assert_eq!(
+            make_http_url(&config, "send_soil_moisture"),
+            "http://example.com/send_soil_moisture"
+        );
+
+        apply_form(&mut config, "url_tpl=https://h/{id}/{name}/{endpoint}").unwrap();
+        assert_eq!(
+            make_http_url(&config, "level"),
+            "https://h/ab12/My%20garden/level"
+        );
+    }
+
+    #[test]
+    fn server_changes_allowed_settings_only() {
+        let mut config = config();
+        apply_form(&mut config, "ssid=Home&pass=secret").unwrap();
+
+        let count = apply_json(
+            &mut config,
+            r#"{"config": {"name": "Tomatoes", "sleep": 600000000, "ssid": "Evil",
+                "pass": "", "ota_url": "http://evil/fw", "unknown": 1}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(config.get_name(), "Tomatoes");
+        assert_eq!(config.get_deep_sleep_duration(), 600_000_000);
+        assert_eq!(config.get_ssid(), "Home");
+        assert_eq!(config.get_passphrase(), "secret");
+        assert_eq!(config.get_ota_url(), "");
+
+        assert_eq!(apply_json(&mut config, r#"{"status": "ok"}"#).unwrap(), 0);
+
+        // Calibration of a sensor left out of the build
+        #[cfg(not(feature = "aht10-sensor"))]
+        assert_eq!(
+            apply_json(
+                &mut config,
+                r#"{"config": {"temp_offset": 1.5, "name": "Peppers"}}"#
+            )
+            .unwrap(),
+            1
+        );
+        assert!(SERVER_SETTINGS
+            .iter()
+            .all(|name| MAP_NVS_FORM.iter().any(|e| e.form_name == *name)));
+    }
+
+    #[test]
+    fn failed_store_restores_raw_values() {
+        let mut store = MemoryConfigStore::new();
+        store.set_str(KEY_NAME, "Tomatoes").unwrap();
+        store.set_str(KEY_MQTT_PASS, "secret").unwrap();
+        store.set_u8(KEY_MQTT_QOS, 2).unwrap();
+
+        let mut config = NvsConfiguration::new(FailingStore {
+            inner: store.clone(),
+            failing_key: KEY_SLEEP,
+        });
+
+        assert!(apply_form(
+            &mut config,
+            "ssid=Home&mqtt_pass=new&mqtt_qos=0&name=Peppers&sleep=600000000"
+        )
+        .is_err());
+
+        // Secrets are not read back as empty nor absent keys as defaults
+        for elem in MAP_NVS_FORM {
+            assert_eq!(
+                config.snapshot(elem.nvs_key),
+                NvsConfiguration::new(store.clone()).snapshot(elem.nvs_key),
+                "{}",
+                elem.form_name
+            );
+        }
+        assert_eq!(
+            config.snapshot(KEY_MQTT_PASS),
+            Some(StoreValue::Str("secret".to_string()))
+        );
+        assert_eq!(config.snapshot(KEY_SSID), None);
+    }
+
+    #[test]
+    fn static_ip_needs_netmask_and_gateway() {
+        let errors = parse_form("static_ip=192.168.1.50&netmask=&dns=").unwrap_err();
+        assert_eq!(
+            errors.to_string(),
+            "netmask: Required with a static IP address, \
+             gateway: Required with a static IP address"
+        );
+
+        assert!(parse_form("static_ip=&netmask=&gateway=").is_ok());
+        assert!(
+            parse_form("static_ip=192.168.1.50&netmask=255.255.255.0&gateway=192.168.1.1").is_ok()
+        );
+    }
+}
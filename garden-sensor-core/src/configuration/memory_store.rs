@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use super::config_store::{ConfigStore, StoreValue};
+
+/// Volatile [`ConfigStore`], for tests and host tools.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryConfigStore {
+    values: HashMap<String, StoreValue>,
+}
+
+impl MemoryConfigStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn values(&self) -> &HashMap<String, StoreValue> {
+        &self.values
+    }
+
+    fn set(&mut self, key: &str, value: StoreValue) -> Result<(), Infallible> {
+        self.values.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+impl ConfigStore for MemoryConfigStore {
+    type Error = Infallible;
+
+    fn get_str(&self, key: &str) -> Option<String> {
+        match self.values.get(key) {
+            Some(StoreValue::Str(v)) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    fn set_str(&mut self, key: &str, value: &str) -> Result<(), Self::Error> {
+        self.set(key, StoreValue::Str(value.to_string()))
+    }
+
+    fn get_u8(&self, key: &str) -> Option<u8> {
+        match self.values.get(key) {
+            Some(StoreValue::U8(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn set_u8(&mut self, key: &str, value: u8) -> Result<(), Self::Error> {
+        self.set(key, StoreValue::U8(value))
+    }
+
+    fn get_u32(&self, key: &str) -> Option<u32> {
+        match self.values.get(key) {
+            Some(StoreValue::U32(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn set_u32(&mut self, key: &str, value: u32) -> Result<(), Self::Error> {
+        self.set(key, StoreValue::U32(value))
+    }
+
+    fn get_u64(&self, key: &str) -> Option<u64> {
+        match self.values.get(key) {
+            Some(StoreValue::U64(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn set_u64(&mut self, key: &str, value: u64) -> Result<(), Self::Error> {
+        self.set(key, StoreValue::U64(value))
+    }
+
+    fn get_f32(&self, key: &str) -> Option<f32> {
+        match self.values.get(key) {
+            Some(StoreValue::F32(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn set_f32(&mut self, key: &str, value: f32) -> Result<(), Self::Error> {
+        self.set(key, StoreValue::F32(value))
+    }
+
+    fn get_blob(&self, key: &str) -> Option<Vec<u8>> {
+        match self.values.get(key) {
+            Some(StoreValue::Blob(v)) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    fn set_blob(&mut self, key: &str, value: &[u8]) -> Result<(), Self::Error> {
+        self.set(key, StoreValue::Blob(value.to_vec()))
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), Self::Error> {
+        self.values.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::config_store::tests::{round_trip, type_isolation};
+
+    #[test]
+    fn typed_round_trip() {
+        round_trip(&mut MemoryConfigStore::new());
+    }
+
+    #[test]
+    fn typed_keys_isolated() {
+        type_isolation(&mut MemoryConfigStore::new());
+    }
+}
@@ -0,0 +1,794 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use pad::{Alignment, PadStr};
+
+use super::config_store::{ConfigStore, StoreValue};
+use crate::change_report::ReportThresholds;
+use crate::string_error::StringError;
+use crate::wifi_networks::{Network, MAX_NETWORKS};
+
+const PAD_CHAR: char = 0x03 as char;
+
+/// SSID, passphrase and security keys of the saved networks, in preference
+/// order
+const NETWORK_KEYS: [(&str, &str, &str); MAX_NETWORKS] = [
+    (KEY_SSID, KEY_PASSPHRASE, KEY_WIFI_AUTH),
+    (KEY_SSID_2, KEY_PASSPHRASE_2, KEY_WIFI_AUTH_2),
+    (KEY_SSID_3, KEY_PASSPHRASE_3, KEY_WIFI_AUTH_3),
+];
+
+pub const KEY_SSID: &str = "SSID";
+pub const KEY_PASSPHRASE: &str = "PASS";
+pub const KEY_AP_PASSWORD: &str = "APPASS";
+pub const KEY_SSID_2: &str = "SSID2";
+pub const KEY_PASSPHRASE_2: &str = "PASS2";
+pub const KEY_SSID_3: &str = "SSID3";
+pub const KEY_PASSPHRASE_3: &str = "PASS3";
+pub const KEY_WIFI_AUTH: &str = "WIFIAUTH";
+pub const KEY_WIFI_AUTH_2: &str = "WIFIAUTH2";
+pub const KEY_WIFI_AUTH_3: &str = "WIFIAUTH3";
+pub const KEY_SERVER_ADDRESS: &str = "SRVADDR";
+pub const KEY_STATIC_IP: &str = "STATICIP";
+pub const KEY_NETMASK: &str = "NETMASK";
+pub const KEY_GATEWAY: &str = "GATEWAY";
+pub const KEY_DNS: &str = "DNS";
+pub const KEY_ID: &str = "ID";
+pub const KEY_NAME: &str = "NAME";
+pub const KEY_SLEEP: &str = "SLEEP";
+pub const KEY_BATCH_SIZE: &str = "BATCHSIZE";
+pub const KEY_REPORT_DELTA: &str = "REPORTDELTA";
+pub const KEY_BATTERY_DELTA: &str = "BATDELTA";
+pub const KEY_HEARTBEAT: &str = "HEARTBEAT";
+pub const KEY_NTP_SERVER: &str = "NTPSERVER";
+pub const KEY_NTP_RESYNC: &str = "NTPRESYNC";
+pub const KEY_OTA_URL: &str = "OTAURL";
+pub const KEY_TX_POWER: &str = "TX_POWER";
+
+pub const KEY_UPLINK: &str = "UPLINK";
+pub const KEY_HTTP_TLS: &str = "HTTPTLS";
+pub const KEY_CA_CERT: &str = "CACERT";
+pub const KEY_URL_TEMPLATE: &str = "URLTPL";
+pub const KEY_HTTP_METHOD: &str = "HTTPMETHOD";
+pub const KEY_HTTP_HEADERS: &str = "HTTPHEADERS";
+pub const KEY_MQTT_HOST: &str = "MQTTHOST";
+pub const KEY_MQTT_PORT: &str = "MQTTPORT";
+pub const KEY_MQTT_USER: &str = "MQTTUSER";
+pub const KEY_MQTT_PASS: &str = "MQTTPASS";
+pub const KEY_MQTT_TOPIC: &str = "MQTTTOPIC";
+pub const KEY_MQTT_QOS: &str = "MQTTQOS";
+pub const KEY_MQTT_RETAIN: &str = "MQTTRETAIN";
+pub const KEY_HA_DISCOVERY: &str = "HADISCOVERY";
+
+pub const KEY_MOIST_ENABLE: &str = "MENABLE";
+pub const KEY_MOIST_PIN_ADC: &str = "MPINADC";
+pub const KEY_MOIST_PIN_EN: &str = "MPINEN";
+pub const KEY_MOIST_VHIGH: &str = "MVHIGH";
+pub const KEY_MOIST_VLOW: &str = "MVLOW";
+
+pub const KEY_WATER_ENABLE: &str = "WENABLE";
+pub const KEY_WATER_PIN_EN: &str = "WPINEN";
+pub const KEY_WATER_PIN_TRIG: &str = "WPINTRIG";
+pub const KEY_WATER_PIN_ECHO: &str = "WPINECHO";
+pub const KEY_WATER_HIGH: &str = "WATERHIGH";
+pub const KEY_WATER_LOW: &str = "WATERLOW";
+
+pub const KEY_AHT_ENABLE: &str = "AHTENABLE";
+pub const KEY_AHT_PIN_SDA: &str = "AHTPINSDA";
+pub const KEY_AHT_PIN_SCL: &str = "AHTPINSCL";
+pub const KEY_AHT_VARIANT: &str = "AHTVARIANT";
+pub const KEY_AHT_TEMP_OFFSET: &str = "AHTTOFFSET";
+pub const KEY_AHT_HUM_OFFSET: &str = "AHTHOFFSET";
+
+/// Security of a Wi-Fi network, from the weakest to the strongest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiAuth {
+    /// Taken from the scan, or WPA2 (open without passphrase) for a network
+    /// not seen by the scan
+    Auto,
+    Open,
+    WpaWpa2,
+    Wpa2,
+    Wpa2Wpa3,
+    Wpa3,
+}
+
+impl From<u8> for WifiAuth {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => WifiAuth::Open,
+            2 => WifiAuth::WpaWpa2,
+            3 => WifiAuth::Wpa2,
+            4 => WifiAuth::Wpa2Wpa3,
+            5 => WifiAuth::Wpa3,
+            _ => WifiAuth::Auto,
+        }
+    }
+}
+
+impl fmt::Display for WifiAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WifiAuth::Auto => "automatic",
+            WifiAuth::Open => "open",
+            WifiAuth::WpaWpa2 => "WPA/WPA2",
+            WifiAuth::Wpa2 => "WPA2",
+            WifiAuth::Wpa2Wpa3 => "WPA2/WPA3",
+            WifiAuth::Wpa3 => "WPA3",
+        })
+    }
+}
+
+/// Fixed IPv4 configuration of the station, used instead of DHCP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StaticIp {
+    pub ip: Ipv4Addr,
+    /// Netmask as a prefix length, e.g. 24 for `255.255.255.0`
+    pub prefix_len: u8,
+    pub gateway: Ipv4Addr,
+    /// The gateway when no DNS server is set
+    pub dns: Ipv4Addr,
+}
+
+/// Transport used to send the readings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uplink {
+    Http,
+    Mqtt,
+}
+
+impl From<u8> for Uplink {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Uplink::Mqtt,
+            _ => Uplink::Http,
+        }
+    }
+}
+
+/// Server certificate check of the HTTP uplink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpTls {
+    /// Plain `http://`
+    None,
+    /// `https://` checked against the ESP-IDF certificate bundle
+    Bundle,
+    /// `https://` checked against the CA certificate stored in `KEY_CA_CERT`
+    CustomCa,
+}
+
+impl From<u8> for HttpTls {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => HttpTls::Bundle,
+            2 => HttpTls::CustomCa,
+            _ => HttpTls::None,
+        }
+    }
+}
+
+/// HTTP method used to send the readings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpMethod {
+    Post,
+    Put,
+}
+
+impl From<u8> for HttpMethod {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => HttpMethod::Put,
+            _ => HttpMethod::Post,
+        }
+    }
+}
+
+/// Sensor calibration used until the settings are saved, shared with the
+/// settings form so both agree. A zero would make the level computation divide
+/// by zero.
+pub const DEFAULT_MOIST_VHIGH: f32 = 1.26;
+pub const DEFAULT_MOIST_VLOW: f32 = 2.55;
+pub const DEFAULT_WATER_HIGH: f32 = 20.0;
+pub const DEFAULT_WATER_LOW: f32 = 1020.0;
+
+/// Water level sensor enable, trigger and echo pins of the firmwares built for
+/// this sensor only, before the sensor profile was stored
+const LEGACY_WATER_PINS: [(&str, u8); 3] = [
+    (KEY_WATER_PIN_EN, 6),
+    (KEY_WATER_PIN_TRIG, 4),
+    (KEY_WATER_PIN_ECHO, 5),
+];
+
+/// Length of the generated settings password, 60 random bits
+pub const AP_PASSWORD_LEN: usize = 12;
+
+const MAX_AP_PASSWORD_LEN: usize = 63;
+
+/// Characters of the generated settings password, without look-alikes
+const PASSWORD_ALPHABET: &[u8; 32] = b"abcdefghijkmnpqrstuvwxyz23456789";
+
+/// Largest accepted PEM CA certificate, in bytes
+pub const MAX_CA_CERT_LEN: usize = 4096;
+
+/// Typed device settings on top of a [`ConfigStore`].
+pub struct NvsConfiguration<S: ConfigStore> {
+    store: S,
+}
+
+impl<S: ConfigStore> NvsConfiguration<S> {
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn get_ssid(&self) -> String {
+        self.read_string(KEY_SSID, "")
+    }
+
+    pub fn get_passphrase(&self) -> String {
+        self.read_string(KEY_PASSPHRASE, "")
+    }
+
+    /// Saved networks with an SSID, in preference order
+    pub fn get_networks(&self) -> Vec<Network> {
+        NETWORK_KEYS
+            .iter()
+            .map(|(ssid, password, auth)| Network {
+                ssid: self.read_string(ssid, ""),
+                password: self.read_string(password, ""),
+                auth: self.read_u8(auth, 0).into(),
+            })
+            .filter(|n| !n.ssid.is_empty())
+            .collect()
+    }
+
+    pub fn get_name(&self) -> String {
+        self.read_string(KEY_NAME, "")
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.read_u32(KEY_ID, 0)
+    }
+
+    /// Password of the settings access point and portal login, empty until
+    /// [`Self::init_ap_password`] stores one. The firmware prints it on the
+    /// serial console whenever settings mode starts, so a forgotten password
+    /// can be read back over USB.
+    pub fn get_ap_password(&self) -> String {
+        self.read_string(KEY_AP_PASSWORD, "")
+    }
+
+    /// Store a password made from `random` bytes when none is set (first
+    /// boot), so the default password cannot be guessed. Returns the
+    /// generated password, `None` when one was already set.
+    pub fn init_ap_password(
+        &mut self,
+        random: &[u8; AP_PASSWORD_LEN],
+    ) -> Result<Option<String>, S::Error> {
+        if !self.get_ap_password().is_empty() {
+            return Ok(None);
+        }
+
+        // 32 characters, so every byte maps to one with the same probability
+        let password: String = random
+            .iter()
+            .map(|b| PASSWORD_ALPHABET[*b as usize % PASSWORD_ALPHABET.len()] as char)
+            .collect();
+
+        self.store_string(KEY_AP_PASSWORD, &password, MAX_AP_PASSWORD_LEN)?;
+
+        Ok(Some(password))
+    }
+
+    /// Static IPv4 configuration of the first saved network, `None` to use
+    /// DHCP: no address set, or the netmask or gateway is missing or invalid.
+    pub fn get_static_ip(&self) -> Option<StaticIp> {
+        let read_ip = |key| Ipv4Addr::from_str(&self.read_string(key, "")).ok();
+
+        let ip = read_ip(KEY_STATIC_IP)?;
+        let mask = read_ip(KEY_NETMASK).map(u32::from);
+        let gateway = read_ip(KEY_GATEWAY);
+
+        // The netmask bits must be contiguous
+        let (Some(mask), Some(gateway)) =
+            (mask.filter(|m| m.leading_ones() == m.count_ones()), gateway)
+        else {
+            log::warn!(
+                "Static IP {} set without a valid netmask and gateway, using DHCP",
+                ip
+            );
+            return None;
+        };
+
+        Some(StaticIp {
+            ip,
+            prefix_len: mask.leading_ones() as u8,
+            gateway,
+            dns: read_ip(KEY_DNS).unwrap_or(gateway),
+        })
+    }
+
+    pub fn get_server_address(&self) -> String {
+        self.read_string(KEY_SERVER_ADDRESS, "192.168.70.1")
+    }
+
+    pub fn get_deep_sleep_duration(&self) -> u64 {
+        self.read_u64(KEY_SLEEP, 3_600_000_000)
+    }
+
+    /// Number of wake-ups whose readings are uploaded together, 1 uploads on
+    /// every wake-up.
+    pub fn get_batch_size(&self) -> u8 {
+        self.read_u8(KEY_BATCH_SIZE, 1).max(1)
+    }
+
+    pub fn get_report_thresholds(&self) -> ReportThresholds {
+        ReportThresholds {
+            delta: self.read_float(KEY_REPORT_DELTA, 0.0),
+            battery_delta: self.read_float(KEY_BATTERY_DELTA, 5.0),
+            heartbeat: self.read_u8(KEY_HEARTBEAT, 12),
+        }
+    }
+
+    pub fn get_ntp_server(&self) -> String {
+        self.read_string(KEY_NTP_SERVER, "pool.ntp.org")
+    }
+
+    pub fn get_ntp_resync_hours(&self) -> u8 {
+        self.read_u8(KEY_NTP_RESYNC, 24)
+    }
+
+    /// Firmware update URL template with `{id}` and `{name}` placeholders,
+    /// empty to disable the update check.
+    pub fn get_ota_url(&self) -> String {
+        self.read_string(KEY_OTA_URL, "")
+    }
+
+    pub fn get_tx_power(&self) -> i8 {
+        self.read_u8(KEY_TX_POWER, 80) as i8
+    }
+
+    pub fn get_uplink(&self) -> Uplink {
+        Uplink::from(self.read_u8(KEY_UPLINK, 0))
+    }
+
+    pub fn get_http_tls(&self) -> HttpTls {
+        HttpTls::from(self.read_u8(KEY_HTTP_TLS, 0))
+    }
+
+    /// Server URL template with `{id}`, `{name}` and `{endpoint}` placeholders,
+    /// empty to use the server address.
+    pub fn get_url_template(&self) -> String {
+        self.read_string(KEY_URL_TEMPLATE, "")
+    }
+
+    pub fn get_http_method(&self) -> HttpMethod {
+        HttpMethod::from(self.read_u8(KEY_HTTP_METHOD, 0))
+    }
+
+    /// Extra request headers, stored one `Name: value` per line.
+    pub fn get_http_headers(&self) -> Vec<(String, String)> {
+        self.read_string(KEY_HTTP_HEADERS, "")
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .filter(|(name, _)| !name.is_empty())
+            .collect()
+    }
+
+    /// Stored PEM CA certificate, NUL terminated as ESP-TLS expects it.
+    pub fn get_ca_certificate(&self) -> Option<Vec<u8>> {
+        self.store.get_blob(KEY_CA_CERT)
+    }
+
+    /// Store a PEM CA certificate, an empty `pem` removes the stored one.
+    pub fn store_ca_certificate(&mut self, pem: &str) -> anyhow::Result<()> {
+        let pem = pem.trim();
+
+        if pem.is_empty() {
+            self.store.remove(KEY_CA_CERT)?;
+            return Ok(());
+        }
+
+        if !pem.starts_with("-----BEGIN CERTIFICATE-----")
+            || !pem.ends_with("-----END CERTIFICATE-----")
+        {
+            return Err(StringError("CA certificate must be in PEM format").into());
+        }
+
+        if pem.len() + 1 > MAX_CA_CERT_LEN {
+            return Err(StringError("CA certificate is too large").into());
+        }
+
+        let mut blob = pem.as_bytes().to_vec();
+        blob.push(0);
+
+        self.store.remove(KEY_CA_CERT)?;
+        self.store.set_blob(KEY_CA_CERT, &blob)?;
+
+        Ok(())
+    }
+
+    pub fn get_mqtt_host(&self) -> String {
+        self.read_string(KEY_MQTT_HOST, "")
+    }
+
+    pub fn get_mqtt_port(&self) -> u16 {
+        self.read_u32(KEY_MQTT_PORT, 1883) as u16
+    }
+
+    pub fn get_mqtt_user(&self) -> String {
+        self.read_string(KEY_MQTT_USER, "")
+    }
+
+    pub fn get_mqtt_password(&self) -> String {
+        self.read_string(KEY_MQTT_PASS, "")
+    }
+
+    pub fn get_mqtt_topic(&self) -> String {
+        self.read_string(KEY_MQTT_TOPIC, "garden/{id}/{endpoint}")
+    }
+
+    pub fn get_mqtt_qos(&self) -> u8 {
+        self.read_u8(KEY_MQTT_QOS, 1).min(2)
+    }
+
+    pub fn is_mqtt_retain(&self) -> bool {
+        self.read_u8(KEY_MQTT_RETAIN, 0) != 0
+    }
+
+    pub fn is_ha_discovery_enabled(&self) -> bool {
+        self.read_u8(KEY_HA_DISCOVERY, 1) != 0
+    }
+
+    pub fn is_moisture_enabled(&self) -> bool {
+        self.read_u8(KEY_MOIST_ENABLE, 1) != 0
+    }
+
+    pub fn get_moisture_pin_adc(&self) -> u8 {
+        self.read_u8(KEY_MOIST_PIN_ADC, 4)
+    }
+
+    pub fn get_moisture_pin_enable(&self) -> u8 {
+        self.read_u8(KEY_MOIST_PIN_EN, 6)
+    }
+
+    pub fn get_vhigh_moisture(&self) -> f32 {
+        self.read_float(KEY_MOIST_VHIGH, DEFAULT_MOIST_VHIGH)
+    }
+
+    pub fn get_vlow_moisture(&self) -> f32 {
+        self.read_float(KEY_MOIST_VLOW, DEFAULT_MOIST_VLOW)
+    }
+
+    pub fn is_water_level_enabled(&self) -> bool {
+        self.read_u8(KEY_WATER_ENABLE, 0) != 0
+    }
+
+    pub fn get_water_level_pin_enable(&self) -> u8 {
+        self.read_u8(KEY_WATER_PIN_EN, 10)
+    }
+
+    pub fn get_water_level_pin_trigger(&self) -> u8 {
+        self.read_u8(KEY_WATER_PIN_TRIG, 2)
+    }
+
+    pub fn get_water_level_pin_echo(&self) -> u8 {
+        self.read_u8(KEY_WATER_PIN_ECHO, 5)
+    }
+
+    pub fn get_high_water_level(&self) -> f32 {
+        self.read_float(KEY_WATER_HIGH, DEFAULT_WATER_HIGH)
+    }
+
+    pub fn get_low_water_level(&self) -> f32 {
+        self.read_float(KEY_WATER_LOW, DEFAULT_WATER_LOW)
+    }
+
+    pub fn is_aht_enabled(&self) -> bool {
+        self.read_u8(KEY_AHT_ENABLE, 0) != 0
+    }
+
+    pub fn get_aht_pin_sda(&self) -> u8 {
+        self.read_u8(KEY_AHT_PIN_SDA, 8)
+    }
+
+    pub fn get_aht_pin_scl(&self) -> u8 {
+        self.read_u8(KEY_AHT_PIN_SCL, 9)
+    }
+
+    pub fn get_aht_variant(&self) -> u8 {
+        self.read_u8(KEY_AHT_VARIANT, 10)
+    }
+
+    pub fn get_temperature_offset(&self) -> f32 {
+        self.read_float(KEY_AHT_TEMP_OFFSET, 0.0)
+    }
+
+    pub fn get_humidity_offset(&self) -> f32 {
+        self.read_float(KEY_AHT_HUM_OFFSET, 0.0)
+    }
+
+    /// Store the sensor profile of a board flashed before the profile was
+    /// stored, when the firmware was built for the moisture or the water
+    /// level sensor only. The calibration saved by its settings form tells
+    /// which one it was. Returns `true` when a profile was stored.
+    pub fn migrate_sensor_profile(&mut self) -> Result<bool, S::Error> {
+        let profile_stored = [KEY_MOIST_ENABLE, KEY_WATER_ENABLE, KEY_AHT_ENABLE]
+            .iter()
+            .any(|key| self.store.get_u8(key).is_some());
+
+        if profile_stored {
+            return Ok(false);
+        }
+
+        let moisture = self.store.get_f32(KEY_MOIST_VHIGH).is_some();
+        let water_level = self.store.get_f32(KEY_WATER_HIGH).is_some();
+
+        match (moisture, water_level) {
+            (false, true) => {
+                self.store_u8(KEY_MOIST_ENABLE, 0)?;
+                self.store_u8(KEY_WATER_ENABLE, 1)?;
+                for (key, pin) in LEGACY_WATER_PINS {
+                    self.store_u8(key, pin)?;
+                }
+            }
+            (true, false) => {
+                self.store_u8(KEY_MOIST_ENABLE, 1)?;
+                self.store_u8(KEY_WATER_ENABLE, 0)?;
+            }
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Value of `key` exactly as stored (strings with their padding), `None`
+    /// if the key is absent. Put it back with [`Self::restore`].
+    pub fn snapshot(&self, key: &str) -> Option<StoreValue> {
+        let store = &self.store;
+
+        store
+            .get_str(key)
+            .map(StoreValue::Str)
+            .or_else(|| store.get_u8(key).map(StoreValue::U8))
+            .or_else(|| store.get_u32(key).map(StoreValue::U32))
+            .or_else(|| store.get_u64(key).map(StoreValue::U64))
+            .or_else(|| store.get_f32(key).map(StoreValue::F32))
+            .or_else(|| store.get_blob(key).map(StoreValue::Blob))
+    }
+
+    /// Put back a value of [`Self::snapshot`], removing `key` if it was absent.
+    pub fn restore(&mut self, key: &str, value: Option<&StoreValue>) -> Result<(), S::Error> {
+        self.store.remove(key)?;
+
+        match value {
+            None => Ok(()),
+            Some(StoreValue::Str(v)) => self.store.set_str(key, v),
+            Some(StoreValue::U8(v)) => self.store.set_u8(key, *v),
+            Some(StoreValue::U32(v)) => self.store.set_u32(key, *v),
+            Some(StoreValue::U64(v)) => self.store.set_u64(key, *v),
+            Some(StoreValue::F32(v)) => self.store.set_f32(key, *v),
+            Some(StoreValue::Blob(v)) => self.store.set_blob(key, v),
+        }
+    }
+
+    pub fn store_string(
+        &mut self,
+        key: &str,
+        value: &str,
+        max_size: usize,
+    ) -> Result<(), S::Error> {
+        self.store.remove(key)?;
+        self.store
+            .set_str(key, &Self::trunc_pad_string(value, max_size))
+    }
+
+    pub fn read_string(&self, key: &str, default: &str) -> String {
+        match self.store.get_str(key) {
+            Some(result) if !result.is_empty() => result
+                .split_once(PAD_CHAR)
+                .unwrap_or((&result, ""))
+                .0
+                .to_owned(),
+            _ => default.to_string(),
+        }
+    }
+
+    pub fn store_float(&mut self, key: &str, value: f32) -> Result<(), S::Error> {
+        self.store.remove(key)?;
+        self.store.set_f32(key, value)
+    }
+
+    pub fn read_float(&self, key: &str, default: f32) -> f32 {
+        self.store.get_f32(key).unwrap_or(default)
+    }
+
+    pub fn store_u8(&mut self, key: &str, value: u8) -> Result<(), S::Error> {
+        self.store.remove(key)?;
+        self.store.set_u8(key, value)
+    }
+
+    pub fn read_u8(&self, key: &str, default: u8) -> u8 {
+        self.store.get_u8(key).unwrap_or(default)
+    }
+
+    pub fn store_u32(&mut self, key: &str, value: u32) -> Result<(), S::Error> {
+        self.store.remove(key)?;
+        self.store.set_u32(key, value)
+    }
+
+    pub fn read_u32(&self, key: &str, default: u32) -> u32 {
+        self.store.get_u32(key).unwrap_or(default)
+    }
+
+    pub fn store_u64(&mut self, key: &str, value: u64) -> Result<(), S::Error> {
+        self.store.remove(key)?;
+        self.store.set_u64(key, value)
+    }
+
+    pub fn read_u64(&self, key: &str, default: u64) -> u64 {
+        self.store.get_u64(key).unwrap_or(default)
+    }
+
+    fn trunc_pad_string(s: &str, max: usize) -> String {
+        s.pad(max, PAD_CHAR, Alignment::Left, true)
+    }
+}
+
+/// Configuration fixtures for the tests.
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::NvsConfiguration;
+    use crate::configuration::memory_store::MemoryConfigStore;
+
+    /// Configuration with nothing stored yet, every getter returns its default
+    pub fn config() -> NvsConfiguration<MemoryConfigStore> {
+        NvsConfiguration::new(MemoryConfigStore::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::config;
+    use super::*;
+
+    #[test]
+    fn string_padded_to_max_size() {
+        let mut config = config();
+        config.store_string(KEY_NAME, "abc", 8).unwrap();
+
+        assert_eq!(
+            config.store.get_str(KEY_NAME).as_deref(),
+            Some("abc\u{3}\u{3}\u{3}\u{3}\u{3}")
+        );
+        assert_eq!(config.read_string(KEY_NAME, "default"), "abc");
+    }
+
+    #[test]
+    fn string_truncated_to_max_size() {
+        let mut config = config();
+        config.store_string(KEY_NAME, "abcdefgh", 4).unwrap();
+
+        assert_eq!(config.store.get_str(KEY_NAME).as_deref(), Some("abcd"));
+        assert_eq!(config.read_string(KEY_NAME, "default"), "abcd");
+    }
+
+    #[test]
+    fn empty_string_reads_default() {
+        let mut config = config();
+        assert_eq!(config.read_string(KEY_NAME, "default"), "default");
+
+        config.store_string(KEY_NAME, "", 0).unwrap();
+        assert_eq!(config.read_string(KEY_NAME, "default"), "default");
+    }
+
+    #[test]
+    fn ca_certificate() {
+        let mut config = config();
+        let pem = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----";
+
+        config
+            .store_ca_certificate(&format!("\n{}\n", pem))
+            .unwrap();
+        let mut expected = pem.as_bytes().to_vec();
+        expected.push(0);
+        assert_eq!(config.get_ca_certificate(), Some(expected));
+
+        assert!(config.store_ca_certificate("not a certificate").is_err());
+        let too_large = format!(
+            "-----BEGIN CERTIFICATE-----{}-----END CERTIFICATE-----",
+            "A".repeat(MAX_CA_CERT_LEN)
+        );
+        assert!(config.store_ca_certificate(&too_large).is_err());
+        assert!(config.get_ca_certificate().is_some());
+
+        config.store_ca_certificate("").unwrap();
+        assert_eq!(config.get_ca_certificate(), None);
+    }
+
+    #[test]
+    fn migrate_water_level_board() {
+        let mut config = config();
+        config.store_float(KEY_WATER_HIGH, 30.0).unwrap();
+
+        assert!(config.migrate_sensor_profile().unwrap());
+        assert!(!config.is_moisture_enabled());
+        assert!(config.is_water_level_enabled());
+        assert_eq!(config.get_water_level_pin_enable(), 6);
+        assert_eq!(config.get_water_level_pin_trigger(), 4);
+        assert_eq!(config.get_water_level_pin_echo(), 5);
+        assert_eq!(config.get_high_water_level(), 30.0);
+    }
+
+    #[test]
+    fn migrate_moisture_board() {
+        let mut config = config();
+        config.store_float(KEY_MOIST_VHIGH, 1.3).unwrap();
+
+        assert!(config.migrate_sensor_profile().unwrap());
+        assert!(config.is_moisture_enabled());
+        assert!(!config.is_water_level_enabled());
+    }
+
+    #[test]
+    fn keep_stored_or_unknown_profile() {
+        let mut config = config();
+        assert!(!config.migrate_sensor_profile().unwrap());
+        assert!(config.store.values().is_empty());
+
+        config.store_float(KEY_WATER_HIGH, 30.0).unwrap();
+        config.store_u8(KEY_WATER_ENABLE, 0).unwrap();
+        assert!(!config.migrate_sensor_profile().unwrap());
+        assert!(!config.is_water_level_enabled());
+    }
+
+    #[test]
+    fn ap_password_generated_once() {
+        let mut config = config();
+        assert_eq!(config.get_ap_password(), "");
+
+        let password = config
+            .init_ap_password(&[0, 1, 31, 32, 255, 7, 8, 9, 10, 11, 12, 13])
+            .unwrap();
+        assert_eq!(password.as_deref(), Some("ab9a9hijkmnp"));
+        assert_eq!(config.get_ap_password(), "ab9a9hijkmnp");
+
+        assert_eq!(
+            config.init_ap_password(&[0; AP_PASSWORD_LEN]).unwrap(),
+            None
+        );
+        assert_eq!(config.get_ap_password(), "ab9a9hijkmnp");
+    }
+
+    #[test]
+    fn static_ip_dns_defaults_to_gateway() {
+        let mut config = config();
+        assert_eq!(config.get_static_ip(), None);
+
+        config
+            .store_string(KEY_STATIC_IP, "192.168.1.50", 15)
+            .unwrap();
+        config
+            .store_string(KEY_NETMASK, "255.255.255.0", 15)
+            .unwrap();
+        config.store_string(KEY_GATEWAY, "192.168.1.1", 15).unwrap();
+
+        let gateway = Ipv4Addr::new(192, 168, 1, 1);
+        assert_eq!(
+            config.get_static_ip(),
+            Some(StaticIp {
+                ip: Ipv4Addr::new(192, 168, 1, 50),
+                prefix_len: 24,
+                gateway,
+                dns: gateway,
+            })
+        );
+
+        config.store_string(KEY_DNS, "9.9.9.9", 15).unwrap();
+        assert_eq!(
+            config.get_static_ip().map(|s| s.dns),
+            Some(Ipv4Addr::new(9, 9, 9, 9))
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use serde_json::{json, Map, Value};
+
+use crate::{payload::fill_placeholders, sensors::sensor::SensorsVec};
+
+pub const DISCOVERY_PREFIX: &str = "homeassistant";
+
+/// Home Assistant MQTT discovery messages `(topic, payload)`, one per value of
+/// every sensor. State topics are built from `topic_template` as the uplink
+/// does, values shared by every payload (e.g. battery) are read from the
+/// first endpoint.
+pub fn discovery_messages(
+    id: u32,
+    name: &str,
+    sensors: &SensorsVec,
+    topic_template: &str,
+) -> Vec<(String, String)> {
+    let node_id = format!("garden_{:x}", id);
+    let device = json!({
+        "identifiers": [node_id],
+        "name": name,
+        "model": "ESP32 garden sensor",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+
+    let first_endpoint = match sensors.iter().find_map(|s| s.http_endpoint()) {
+        Some(e) => e,
+        None => return Vec::new(),
+    };
+
+    let mut messages = Vec::new();
+
+    for sensor in sensors {
+        let (endpoint, object_prefix) = match sensor.http_endpoint() {
+            Some(e) => (e, format!("{}_", e)),
+            None => (first_endpoint, String::new()),
+        };
+        let state_topic = fill_placeholders(topic_template, id, name, endpoint);
+
+        for meta in sensor.values_meta() {
+            let object_id = format!("{}{}", object_prefix, meta.key);
+
+            let mut config = Map::new();
+            config.insert("name".to_string(), json!(meta.name));
+            config.insert(
+                "unique_id".to_string(),
+                json!(format!("{}_{}", node_id, object_id)),
+            );
+            config.insert("state_topic".to_string(), json!(state_topic));
+            config.insert(
+                "value_template".to_string(),
+                json!(format!("{{{{ value_json.{} }}}}", meta.key)),
+            );
+            config.insert("state_class".to_string(), json!("measurement"));
+            if let Some(unit) = meta.unit {
+                config.insert("unit_of_measurement".to_string(), json!(unit));
+            }
+            if let Some(device_class) = meta.device_class {
+                config.insert("device_class".to_string(), json!(device_class));
+            }
+            config.insert("device".to_string(), device.clone());
+
+            messages.push((
+                format!(
+                    "{}/sensor/{}/{}/config",
+                    DISCOVERY_PREFIX, node_id, object_id
+                ),
+                Value::Object(config).to_string(),
+            ));
+        }
+    }
+
+    messages
+}
+
+/// Hash of discovery `messages` (FNV-1a). They are retained by the broker, so
+/// only published again when it changes (e.g. sensors, name or firmware
+/// version).
+pub fn discovery_signature(messages: &[(String, String)]) -> u32 {
+    let mut signature: u32 = 0x811c_9dc5;
+
+    for (topic, payload) in messages {
+        // Separators, so moving bytes between topic and payload changes it
+        for bytes in [topic.as_bytes(), &[0], payload.as_bytes(), &[0]] {
+            for byte in bytes {
+                signature ^= *byte as u32;
+                signature = signature.wrapping_mul(0x0100_0193);
+            }
+        }
+    }
+
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::battery_sensor::{self, BatterySensor};
+    use crate::sensors::hal::mock::MockAdc;
+    use crate::sensors::sensor::{Sensor, ValueMeta};
+
+    const TOPIC: &str = "garden/{id}/{endpoint}";
+
+    const LEVEL: &[ValueMeta] = &[ValueMeta {
+        key: "level",
+        name: "Water level",
+        unit: Some("%"),
+        device_class: None,
+    }];
+
+    struct LevelSensor(&'static str);
+
+    impl Sensor for LevelSensor {
+        fn add_json_value(&mut self, _map: &mut Map<String, Value>) {}
+
+        fn pretty_print(&mut self) -> String {
+            String::new()
+        }
+
+        fn http_endpoint(&self) -> Option<&'static str> {
+            Some(self.0)
+        }
+
+        fn values_meta(&self) -> &'static [ValueMeta] {
+            LEVEL
+        }
+    }
+
+    fn sensors(endpoints: &[&'static str]) -> SensorsVec {
+        let mut sensors: SensorsVec = vec![Box::new(BatterySensor::new(MockAdc::constant(1850)))];
+        for endpoint in endpoints {
+            sensors.push(Box::new(LevelSensor(endpoint)));
+        }
+        sensors
+    }
+
+    fn config(payload: &str) -> Value {
+        serde_json::from_str(payload).unwrap()
+    }
+
+    #[test]
+    fn one_message_per_value() {
+        let messages = discovery_messages(0xab12, "Tomatoes", &sensors(&["water"]), TOPIC);
+        let topics: Vec<&str> = messages.iter().map(|(t, _)| t.as_str()).collect();
+
+        assert_eq!(
+            topics,
+            [
+                "homeassistant/sensor/garden_ab12/battery/config",
+                "homeassistant/sensor/garden_ab12/water_level/config",
+            ]
+        );
+
+        let level = config(&messages[1].1);
+        assert_eq!(level["name"], "Water level");
+        assert_eq!(level["unique_id"], "garden_ab12_water_level");
+        assert_eq!(level["state_topic"], "garden/ab12/water");
+        assert_eq!(level["value_template"], "{{ value_json.level }}");
+        assert_eq!(level["unit_of_measurement"], "%");
+        assert!(level.get("device_class").is_none());
+        assert_eq!(level["device"]["identifiers"][0], "garden_ab12");
+        assert_eq!(level["device"]["name"], "Tomatoes");
+    }
+
+    #[test]
+    fn battery_on_first_endpoint_only() {
+        let messages = discovery_messages(1, "Garden", &sensors(&["water", "soil"]), TOPIC);
+        let battery: Vec<Value> = messages
+            .iter()
+            .map(|(_, payload)| config(payload))
+            .filter(|c| c["device_class"] == battery_sensor::VALUES[0].device_class.unwrap())
+            .collect();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(battery.len(), 1);
+        assert_eq!(battery[0]["unique_id"], "garden_1_battery");
+        assert_eq!(battery[0]["state_topic"], "garden/1/water");
+        assert_eq!(config(&messages[2].1)["state_topic"], "garden/1/soil");
+    }
+
+    #[test]
+    fn no_endpoint_no_message() {
+        assert!(discovery_messages(1, "Garden", &sensors(&[]), TOPIC).is_empty());
+    }
+
+    #[test]
+    fn signature_follows_messages() {
+        let messages = discovery_messages(1, "Garden", &sensors(&["water"]), TOPIC);
+        let signature = discovery_signature(&messages);
+
+        assert_eq!(
+            discovery_signature(&discovery_messages(
+                1,
+                "Garden",
+                &sensors(&["water"]),
+                TOPIC
+            )),
+            signature
+        );
+        assert_ne!(
+            discovery_signature(&discovery_messages(1, "Shed", &sensors(&["water"]), TOPIC)),
+            signature
+        );
+        assert_ne!(discovery_signature(&messages[..1]), signature);
+        assert_ne!(
+            discovery_signature(&[("ab".to_string(), "c".to_string())]),
+            discovery_signature(&[("a".to_string(), "bc".to_string())])
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! Hardware independent part of the garden sensor firmware: sensor drivers and
+//! math, JSON payload, settings form mapping and HTML rendering.
+//!
+//! Everything here builds on the host, run the test suite with `cargo test-core`.
+
+pub mod sensors {
+    pub mod aht10_sensor;
+    pub mod battery_sensor;
+    pub mod hal;
+    pub mod hcsr04_sensor;
+    pub mod moisture_sensor;
+    pub mod sensor;
+}
+
+pub mod configuration {
+    pub mod config_store;
+    pub mod file_store;
+    pub mod main_configuration;
+    pub mod memory_store;
+    pub mod nvs_configuration;
+}
+
+pub mod api;
+pub mod auth;
+pub mod captive_dns;
+pub mod change_report;
+pub mod home_assistant;
+pub mod ota_manifest;
+pub mod payload;
+pub mod reading_buffer;
+pub mod sha256;
+pub mod string_error;
+pub mod template;
+pub mod wifi_networks;
@@ -0,0 +1,113 @@
+//! Firmware offered by the update server. The update URL answers a JSON
+//! manifest, e.g.
+//! `{"version": "1.2.0", "url": "https://example.com/garden.bin", "sha256": "<64 hex digits>"}`,
+//! and the image is only booted if its digest matches.
+
+use serde_json::Value;
+
+use crate::string_error::StringError;
+
+/// Firmware described by the update manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateManifest {
+    pub version: String,
+    /// Where to download the image, always HTTPS
+    pub url: String,
+    /// SHA-256 digest of the image
+    pub sha256: [u8; 32],
+}
+
+impl UpdateManifest {
+    pub fn parse(body: &str) -> Result<Self, StringError> {
+        let manifest: Value =
+            serde_json::from_str(body).map_err(|_| StringError("Update manifest is not JSON"))?;
+        let field = |key| {
+            manifest.get(key).and_then(Value::as_str).ok_or(StringError(
+                "Update manifest lacks 'version', 'url' or 'sha256'",
+            ))
+        };
+
+        let url = field("url")?;
+        require_https(url)?;
+
+        Ok(Self {
+            version: field("version")?.to_string(),
+            url: url.to_string(),
+            sha256: parse_digest(field("sha256")?)
+                .ok_or(StringError("Update manifest 'sha256' is not 64 hex digits"))?,
+        })
+    }
+}
+
+/// Firmware is only downloaded over HTTPS, so the server is authenticated.
+pub fn require_https(url: &str) -> Result<(), StringError> {
+    let https = url
+        .get(..8)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"));
+
+    if https {
+        Ok(())
+    } else {
+        Err(StringError("Firmware update URLs must use HTTPS"))
+    }
+}
+
+fn parse_digest(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut digest = [0u8; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+
+    Some(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha256::sha256;
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn valid_manifest() {
+        let manifest = UpdateManifest::parse(&format!(
+            r#"{{"version":"1.2.0","url":"https://example.com/fw.bin","sha256":"{}"}}"#,
+            ABC_SHA256.to_uppercase()
+        ))
+        .unwrap();
+
+        assert_eq!(manifest.version, "1.2.0");
+        assert_eq!(manifest.url, "https://example.com/fw.bin");
+        assert_eq!(manifest.sha256, sha256(b"abc"));
+    }
+
+    #[test]
+    fn invalid_manifests() {
+        for body in [
+            "firmware",
+            r#"{"version":"1.2.0","url":"https://example.com/fw.bin"}"#,
+            &format!(
+                r#"{{"version":"1.2.0","url":"http://example.com/fw.bin","sha256":"{}"}}"#,
+                ABC_SHA256
+            ),
+            &format!(
+                r#"{{"version":"1.2.0","url":"https://example.com/fw.bin","sha256":"{}"}}"#,
+                &ABC_SHA256[1..]
+            ),
+            r#"{"version":"1.2.0","url":"https://example.com/fw.bin","sha256":"zz"}"#,
+        ] {
+            assert!(UpdateManifest::parse(body).is_err(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn https_only() {
+        assert!(require_https("HTTPS://example.com/ota").is_ok());
+        assert!(require_https("http://example.com/ota").is_err());
+        assert!(require_https("example.com").is_err());
+    }
+}
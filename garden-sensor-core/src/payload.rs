@@ -0,0 +1,308 @@
+use serde_json::{json, Map, Value};
+
+use crate::reading_buffer::Reading;
+use crate::sensors::sensor::SensorsVec;
+
+/// Earliest timestamp considered valid (2020-01-01), the clock reads 1970
+/// until it is synchronized.
+pub const MIN_VALID_TIMESTAMP: u64 = 1_577_836_800;
+
+/// Most values in a payload: the battery and the values of one sensor
+pub const MAX_PAYLOAD_VALUES: usize = 3;
+
+/// Longest value key
+pub const MAX_VALUE_KEY_LEN: usize = 11;
+
+/// Longest JSON number of a value: `f32` values are written as the nearest
+/// `f64` (e.g. `-0.000010000058864534367`)
+const MAX_VALUE_LEN: usize = 24;
+
+/// Longest payload returned by [`values_payload`], `{"key":value,...}`
+pub const MAX_VALUES_PAYLOAD_LEN: usize =
+    2 + MAX_PAYLOAD_VALUES * (MAX_VALUE_KEY_LEN + 4 + MAX_VALUE_LEN);
+
+/// Keys of a payload that are not sensor values
+const IDENTITY_KEYS: [&str; 3] = ["id", "name", "timestamp"];
+
+/// `timestamp` if the clock it was read from is synchronized.
+pub fn valid_timestamp(timestamp: u64) -> Option<u64> {
+    (timestamp >= MIN_VALID_TIMESTAMP).then_some(timestamp)
+}
+
+/// JSON document sent to `endpoint`: device identity, time of the reading
+/// (Unix epoch seconds, if known), values shared by every endpoint (e.g.
+/// battery) and the values of the sensors reporting there.
+pub fn generate_json(
+    id: u32,
+    name: &str,
+    sensors: &mut SensorsVec,
+    endpoint: &str,
+    timestamp: Option<u64>,
+) -> Value {
+    let mut map = Map::new();
+
+    map.insert("id".to_string(), json!(id));
+    map.insert("name".to_string(), json!(name));
+    if let Some(timestamp) = timestamp {
+        map.insert("timestamp".to_string(), json!(timestamp));
+    }
+
+    for sensor in sensors {
+        match sensor.http_endpoint() {
+            Some(e) if e != endpoint => continue,
+            _ => sensor.add_json_value(&mut map),
+        }
+    }
+
+    Value::Object(map)
+}
+
+/// Replace the `{id}` (hexadecimal, as in the settings form), `{name}` and
+/// `{endpoint}` placeholders of a topic or URL template.
+pub fn fill_placeholders(template: &str, id: u32, name: &str, endpoint: &str) -> String {
+    template
+        .replace("{id}", &format!("{:x}", id))
+        .replace("{name}", name)
+        .replace("{endpoint}", endpoint)
+}
+
+/// Add `timestamp` to a payload that has none, payloads that are not a JSON
+/// object are returned unchanged.
+pub fn add_timestamp(payload: &str, timestamp: u64) -> String {
+    match serde_json::from_str::<Value>(payload) {
+        Ok(Value::Object(mut map)) => {
+            map.entry("timestamp").or_insert(json!(timestamp));
+            Value::Object(map).to_string()
+        }
+        _ => payload.to_string(),
+    }
+}
+
+/// Sensor values of a payload, small enough to be buffered: the device
+/// identity and time are added back by [`buffered_json`]. `None` if the
+/// payload is not a JSON object.
+pub fn values_payload(payload: &str) -> Option<String> {
+    let mut map = match serde_json::from_str(payload).ok()? {
+        Value::Object(map) => map,
+        _ => return None,
+    };
+
+    for key in IDENTITY_KEYS {
+        map.remove(key);
+    }
+
+    Some(Value::Object(map).to_string())
+}
+
+/// Payload of a buffered reading: its values, the current device identity
+/// and its `timestamp` if the clock was synchronized when it was buffered.
+/// `None` if the stored payload is not a JSON object.
+pub fn buffered_json(reading: &Reading, id: u32, name: &str) -> Option<Value> {
+    let mut value: Value = serde_json::from_str(reading.payload).ok()?;
+    let map = value.as_object_mut()?;
+
+    map.insert("id".to_string(), json!(id));
+    map.insert("name".to_string(), json!(name));
+    if let Some(timestamp) = valid_timestamp(reading.timestamp) {
+        map.insert("timestamp".to_string(), json!(timestamp));
+    }
+
+    Some(value)
+}
+
+/// Group buffered readings by endpoint into JSON arrays, oldest first.
+pub fn batch_json<'a>(
+    readings: impl Iterator<Item = Reading<'a>>,
+    id: u32,
+    name: &str,
+) -> Vec<(String, Value)> {
+    let mut batches: Vec<(String, Vec<Value>)> = Vec::new();
+
+    for reading in readings {
+        let value = match buffered_json(&reading, id, name) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        match batches.iter_mut().find(|(e, _)| e == reading.endpoint) {
+            Some((_, values)) => values.push(value),
+            None => batches.push((reading.endpoint.to_string(), vec![value])),
+        }
+    }
+
+    batches
+        .into_iter()
+        .map(|(endpoint, values)| (endpoint, Value::Array(values)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::reading_buffer::PAYLOAD_LEN;
+    use crate::sensors::sensor::mock::FakeSensor;
+    use crate::sensors::{aht10_sensor, battery_sensor, hcsr04_sensor, moisture_sensor};
+
+    fn sensors() -> SensorsVec {
+        vec![
+            Box::new(FakeSensor {
+                endpoint: None,
+                key: "battery",
+                value: 80.0,
+            }),
+            Box::new(FakeSensor {
+                endpoint: Some("send_soil_moisture"),
+                key: "level",
+                value: 40.0,
+            }),
+            Box::new(FakeSensor {
+                endpoint: Some("send_water_level"),
+                key: "measure",
+                value: 170.0,
+            }),
+        ]
+    }
+
+    #[test]
+    fn json_of_endpoint() {
+        let payload = generate_json(
+            0xab12,
+            "Tomatoes",
+            &mut sensors(),
+            "send_soil_moisture",
+            Some(MIN_VALID_TIMESTAMP),
+        );
+
+        assert_eq!(
+            payload,
+            json!({
+                "id": 0xab12,
+                "name": "Tomatoes",
+                "timestamp": MIN_VALID_TIMESTAMP,
+                "battery": 80.0,
+                "level": 40.0,
+            })
+        );
+    }
+
+    #[test]
+    fn json_without_timestamp() {
+        let payload = generate_json(1, "", &mut sensors(), "send_water_level", None);
+
+        assert_eq!(
+            payload,
+            json!({"id": 1, "name": "", "battery": 80.0, "measure": 170.0})
+        );
+    }
+
+    #[test]
+    fn timestamp_validity() {
+        assert_eq!(valid_timestamp(0), None);
+        assert_eq!(
+            valid_timestamp(MIN_VALID_TIMESTAMP),
+            Some(MIN_VALID_TIMESTAMP)
+        );
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            fill_placeholders("garden/{id}/{name}/{endpoint}", 0xab12, "Tomatoes", "level"),
+            "garden/ab12/Tomatoes/level"
+        );
+    }
+
+    #[test]
+    fn buffered_values_round_trip() {
+        let payload = generate_json(
+            0xab12,
+            "Tomatoes",
+            &mut sensors(),
+            "send_soil_moisture",
+            Some(MIN_VALID_TIMESTAMP),
+        );
+        let values = values_payload(&payload.to_string()).unwrap();
+
+        assert_eq!(values, json!({"battery": 80.0, "level": 40.0}).to_string());
+
+        let reading = Reading {
+            timestamp: MIN_VALID_TIMESTAMP,
+            endpoint: "send_soil_moisture",
+            payload: &values,
+        };
+        assert_eq!(buffered_json(&reading, 0xab12, "Tomatoes"), Some(payload));
+        assert_eq!(values_payload("[1]"), None);
+    }
+
+    #[test]
+    fn batches_by_endpoint() {
+        let readings = [
+            (1, "a", r#"{"level":1.0}"#),
+            (2, "b", r#"{"measure":2.0}"#),
+            (3, "a", r#"{"level":3.0}"#),
+        ];
+        let batches = batch_json(
+            readings
+                .iter()
+                .map(|(timestamp, endpoint, payload)| Reading {
+                    timestamp: *timestamp,
+                    endpoint,
+                    payload,
+                }),
+            1,
+            "",
+        );
+
+        assert_eq!(
+            batches,
+            vec![
+                (
+                    "a".to_string(),
+                    json!([
+                        {"id": 1, "name": "", "level": 1.0},
+                        {"id": 1, "name": "", "level": 3.0},
+                    ])
+                ),
+                (
+                    "b".to_string(),
+                    json!([{"id": 1, "name": "", "measure": 2.0}])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn longest_values_fit_a_buffer_slot() {
+        let longest = [
+            f32::MIN,
+            -f32::MIN_POSITIVE,
+            -f32::EPSILON,
+            -1.0e-45,
+            -123_456_790.0,
+            -0.000_012_345_678,
+            -0.000_010_000_059,
+        ];
+        for value in longest {
+            let json = json!(value).to_string();
+            assert!(json.len() <= MAX_VALUE_LEN, "{}", json);
+        }
+
+        for sensor in [
+            moisture_sensor::VALUES,
+            hcsr04_sensor::VALUES,
+            aht10_sensor::VALUES,
+        ] {
+            let values: Vec<_> = battery_sensor::VALUES.iter().chain(sensor).collect();
+            assert!(values.len() <= MAX_PAYLOAD_VALUES);
+
+            let mut map = Map::new();
+            for meta in values {
+                assert!(meta.key.len() <= MAX_VALUE_KEY_LEN, "{}", meta.key);
+                map.insert(meta.key.to_string(), json!(-0.000_010_000_059_f32));
+            }
+            assert!(Value::Object(map).to_string().len() <= PAYLOAD_LEN);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::str::from_utf8;
+
+use crate::payload::MAX_VALUES_PAYLOAD_LEN;
+
+pub const ENDPOINT_LEN: usize = 32;
+/// Room for the sensor values of a reading, see
+/// [`crate::payload::values_payload`]
+pub const PAYLOAD_LEN: usize = MAX_VALUES_PAYLOAD_LEN;
+
+// The payload length is stored as a `u8`
+const _: () = assert!(PAYLOAD_LEN <= u8::MAX as usize);
+
+/// Changed with the slot layout, so a buffer left by a previous firmware is
+/// reset instead of misread
+const MAGIC: u32 = 0x6761_7265;
+
+#[derive(Clone, Copy)]
+struct Slot {
+    timestamp: u64,
+    endpoint_len: u8,
+    endpoint: [u8; ENDPOINT_LEN],
+    payload_len: u8,
+    payload: [u8; PAYLOAD_LEN],
+}
+
+impl Slot {
+    const EMPTY: Slot = Slot {
+        timestamp: 0,
+        endpoint_len: 0,
+        endpoint: [0; ENDPOINT_LEN],
+        payload_len: 0,
+        payload: [0; PAYLOAD_LEN],
+    };
+}
+
+/// Reading waiting to be uploaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading<'a> {
+    /// Seconds since the Unix epoch, as known by the device clock
+    pub timestamp: u64,
+    pub endpoint: &'a str,
+    pub payload: &'a str,
+}
+
+/// Fixed size ring buffer of JSON readings. When full, the oldest reading is
+/// overwritten.
+///
+/// The buffer holds no pointer, so it may be placed in memory that is not
+/// initialized at boot: call [`ReadingBuffer::validate`] before using it.
+pub struct ReadingBuffer<const N: usize> {
+    magic: u32,
+    head: usize,
+    len: usize,
+    slots: [Slot; N],
+}
+
+impl<const N: usize> ReadingBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            magic: MAGIC,
+            head: 0,
+            len: 0,
+            slots: [Slot::EMPTY; N],
+        }
+    }
+
+    /// Reset the buffer if its content is not consistent (e.g. first boot
+    /// with uninitialized memory).
+    pub fn validate(&mut self) {
+        let consistent = self.magic == MAGIC
+            && self.head < N
+            && self.len <= N
+            && self.slots.iter().all(|s| {
+                (s.endpoint_len as usize) <= ENDPOINT_LEN && (s.payload_len as usize) <= PAYLOAD_LEN
+            });
+
+        if !consistent {
+            *self = Self::new();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Queue a reading, returns `false` if the endpoint or payload does not fit
+    /// in a slot.
+    pub fn push(&mut self, timestamp: u64, endpoint: &str, payload: &str) -> bool {
+        if N == 0 || endpoint.len() > ENDPOINT_LEN || payload.len() > PAYLOAD_LEN {
+            return false;
+        }
+
+        let index = (self.head + self.len) % N;
+        let slot = &mut self.slots[index];
+
+        slot.timestamp = timestamp;
+        slot.endpoint_len = endpoint.len() as u8;
+        slot.endpoint[..endpoint.len()].copy_from_slice(endpoint.as_bytes());
+        slot.payload_len = payload.len() as u8;
+        slot.payload[..payload.len()].copy_from_slice(payload.as_bytes());
+
+        if self.is_full() {
+            self.head = (self.head + 1) % N;
+        } else {
+            self.len += 1;
+        }
+
+        true
+    }
+
+    /// Keep only the readings for which `keep` returns `true`, in order.
+    /// Slots that are not valid UTF-8 are dropped.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Reading) -> bool) {
+        let mut kept = 0;
+
+        for i in 0..self.len {
+            let index = (self.head + i) % N;
+            let keep = Self::reading(&self.slots[index]).is_some_and(|r| keep(&r));
+
+            if keep {
+                self.slots[(self.head + kept) % N] = self.slots[index];
+                kept += 1;
+            }
+        }
+
+        self.len = kept;
+    }
+
+    /// Buffered readings, oldest first. Slots that are not valid UTF-8 are
+    /// skipped.
+    pub fn iter(&self) -> impl Iterator<Item = Reading<'_>> {
+        (0..self.len).filter_map(move |i| Self::reading(&self.slots[(self.head + i) % N]))
+    }
+
+    fn reading(slot: &Slot) -> Option<Reading<'_>> {
+        Some(Reading {
+            timestamp: slot.timestamp,
+            endpoint: from_utf8(&slot.endpoint[..slot.endpoint_len as usize]).ok()?,
+            payload: from_utf8(&slot.payload[..slot.payload_len as usize]).ok()?,
+        })
+    }
+}
+
+impl<const N: usize> Default for ReadingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents<const N: usize>(buffer: &ReadingBuffer<N>) -> Vec<(u64, &str, &str)> {
+        buffer
+            .iter()
+            .map(|r| (r.timestamp, r.endpoint, r.payload))
+            .collect()
+    }
+
+    #[test]
+    fn oldest_overwritten_when_full() {
+        let mut buffer = ReadingBuffer::<2>::new();
+
+        assert!(buffer.push(1, "a", "{}"));
+        assert!(buffer.push(2, "b", "{}"));
+        assert!(buffer.is_full());
+        assert!(buffer.push(3, "c", "{}"));
+
+        assert_eq!(contents(&buffer), [(2, "b", "{}"), (3, "c", "{}")]);
+    }
+
+    #[test]
+    fn too_long_rejected() {
+        let mut buffer = ReadingBuffer::<2>::new();
+
+        assert!(!buffer.push(1, &"e".repeat(ENDPOINT_LEN + 1), "{}"));
+        assert!(!buffer.push(1, "a", &"p".repeat(PAYLOAD_LEN + 1)));
+        assert!(buffer.push(1, "a", &"p".repeat(PAYLOAD_LEN)));
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[test]
+    fn retain_across_wrap() {
+        let mut buffer = ReadingBuffer::<3>::new();
+        for (timestamp, endpoint) in [(1, "a"), (2, "b"), (3, "a"), (4, "b")] {
+            buffer.push(timestamp, endpoint, "{}");
+        }
+
+        buffer.retain(|r| r.endpoint == "b");
+        assert_eq!(contents(&buffer), [(2, "b", "{}"), (4, "b", "{}")]);
+
+        buffer.push(5, "a", "{}");
+        buffer.push(6, "a", "{}");
+        assert_eq!(
+            contents(&buffer),
+            [(4, "b", "{}"), (5, "a", "{}"), (6, "a", "{}")]
+        );
+
+        buffer.retain(|_| false);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn invalid_memory_reset() {
+        let mut buffer = ReadingBuffer::<2>::new();
+        buffer.push(1, "a", "{}");
+        buffer.magic = 0;
+
+        buffer.validate();
+        assert!(buffer.is_empty());
+    }
+}
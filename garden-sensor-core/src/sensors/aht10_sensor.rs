@@ -0,0 +1,361 @@
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{Error, I2c},
+};
+use serde_json::json;
+
+use crate::string_error::{StringError, StringI2cError};
+
+use super::sensor::{Sensor, ValueMeta};
+
+const AHT_ADDRESS: u8 = 0x38;
+
+const CMD_INIT_AHT10: [u8; 3] = [0xE1, 0x08, 0x00];
+const CMD_INIT_AHT20: [u8; 3] = [0xBE, 0x08, 0x00];
+const CMD_TRIGGER: [u8; 3] = [0xAC, 0x33, 0x00];
+const CMD_SOFT_RESET: [u8; 1] = [0xBA];
+
+const STATUS_BUSY: u8 = 0x80;
+const STATUS_CALIBRATED: u8 = 0x08;
+
+const MAX_BUSY_POLL: u8 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AhtVariant {
+    AHT10,
+    AHT20,
+}
+
+impl From<u8> for AhtVariant {
+    fn from(value: u8) -> Self {
+        match value {
+            20 => AhtVariant::AHT20,
+            _ => AhtVariant::AHT10,
+        }
+    }
+}
+
+/// Values added to the payload
+pub const VALUES: &[ValueMeta] = &[
+    ValueMeta {
+        key: "temperature",
+        name: "Temperature",
+        unit: Some("°C"),
+        device_class: Some("temperature"),
+    },
+    ValueMeta {
+        key: "humidity",
+        name: "Humidity",
+        unit: Some("%"),
+        device_class: Some("humidity"),
+    },
+];
+
+pub struct AHT10Sensor<I: I2c, D: DelayNs> {
+    i2c: I,
+    delay: D,
+    variant: AhtVariant,
+    temperature_offset: f32,
+    humidity_offset: f32,
+}
+
+impl<I: I2c, D: DelayNs> AHT10Sensor<I, D> {
+    pub fn new(
+        i2c: I,
+        delay: D,
+        variant: AhtVariant,
+        temperature_offset: f32,
+        humidity_offset: f32,
+    ) -> anyhow::Result<Self> {
+        let mut s = Self {
+            i2c,
+            delay,
+            variant,
+            temperature_offset,
+            humidity_offset,
+        };
+
+        // Power-on time before the sensor accepts commands
+        s.delay.delay_ms(40);
+        s.init()?;
+
+        Ok(s)
+    }
+
+    fn init(&mut self) -> anyhow::Result<()> {
+        self.i2c
+            .write(AHT_ADDRESS, &CMD_SOFT_RESET)
+            .map_err(|e| StringI2cError("Failed to reset AHT sensor", e.kind()))?;
+        self.delay.delay_ms(20);
+
+        if self.read_status()? & STATUS_CALIBRATED != 0 {
+            return Ok(());
+        }
+
+        let cmd_init = match self.variant {
+            AhtVariant::AHT10 => &CMD_INIT_AHT10,
+            AhtVariant::AHT20 => &CMD_INIT_AHT20,
+        };
+
+        self.i2c
+            .write(AHT_ADDRESS, cmd_init)
+            .map_err(|e| StringI2cError("Failed to calibrate AHT sensor", e.kind()))?;
+        self.delay.delay_ms(10);
+
+        if self.read_status()? & STATUS_CALIBRATED == 0 {
+            return Err(StringError("AHT sensor is not calibrated").into());
+        }
+
+        Ok(())
+    }
+
+    fn read_status(&mut self) -> anyhow::Result<u8> {
+        let mut status = [0u8; 1];
+
+        self.i2c
+            .read(AHT_ADDRESS, &mut status)
+            .map_err(|e| StringI2cError("Failed to read AHT status", e.kind()))?;
+
+        Ok(status[0])
+    }
+
+    fn wait_not_busy(&mut self) -> anyhow::Result<()> {
+        for _ in 0..MAX_BUSY_POLL {
+            if self.read_status()? & STATUS_BUSY == 0 {
+                return Ok(());
+            }
+            self.delay.delay_ms(10);
+        }
+
+        Err(StringError("AHT sensor still busy").into())
+    }
+
+    /// Trigger a measurement and return `(temperature °C, relative humidity %)`
+    pub fn read_values(&mut self) -> anyhow::Result<(f32, f32)> {
+        self.i2c
+            .write(AHT_ADDRESS, &CMD_TRIGGER)
+            .map_err(|e| StringI2cError("Failed to trigger AHT measurement", e.kind()))?;
+        self.delay.delay_ms(80);
+
+        self.wait_not_busy()?;
+
+        // The AHT20 appends a CRC byte to the 6 bytes of the AHT10 frame
+        let mut data = [0u8; 7];
+        let frame_len = match self.variant {
+            AhtVariant::AHT10 => 6,
+            AhtVariant::AHT20 => 7,
+        };
+
+        self.i2c
+            .read(AHT_ADDRESS, &mut data[0..frame_len])
+            .map_err(|e| StringI2cError("Failed to read AHT measurement", e.kind()))?;
+
+        if data[0] & STATUS_BUSY != 0 {
+            return Err(StringError("AHT measurement not ready").into());
+        }
+
+        if self.variant == AhtVariant::AHT20 && Self::crc8(&data[0..6]) != data[6] {
+            return Err(StringError("AHT measurement CRC mismatch").into());
+        }
+
+        let raw_humidity =
+            ((data[1] as u32) << 12) | ((data[2] as u32) << 4) | ((data[3] as u32) >> 4);
+        let raw_temperature =
+            (((data[3] as u32) & 0x0F) << 16) | ((data[4] as u32) << 8) | (data[5] as u32);
+
+        let humidity = raw_humidity as f32 * 100.0 / 1_048_576.0;
+        let temperature = raw_temperature as f32 * 200.0 / 1_048_576.0 - 50.0;
+
+        Ok((
+            temperature + self.temperature_offset,
+            (humidity + self.humidity_offset).clamp(0.0, 100.0),
+        ))
+    }
+
+    /// CRC-8, polynomial 0x31, initial value 0xFF
+    fn crc8(data: &[u8]) -> u8 {
+        let mut crc: u8 = 0xFF;
+
+        for byte in data {
+            crc ^= byte;
+            for _ in 0..8 {
+                crc = if crc & 0x80 != 0 {
+                    (crc << 1) ^ 0x31
+                } else {
+                    crc << 1
+                };
+            }
+        }
+
+        crc
+    }
+}
+
+impl<I: I2c, D: DelayNs> Sensor for AHT10Sensor<I, D> {
+    fn add_json_value(&mut self, map: &mut serde_json::Map<String, serde_json::Value>) {
+        match self.read_values() {
+            Ok((temperature, humidity)) => {
+                map.insert("temperature".to_string(), json!(temperature));
+                map.insert("humidity".to_string(), json!(humidity));
+            }
+            Err(e) => log::warn!("Failed to read AHT sensor: {}", e),
+        }
+    }
+
+    fn pretty_print(&mut self) -> String {
+        match self.read_values() {
+            Ok((temperature, humidity)) => format!(
+                "Temperature: {} °C, Humidity: {}% ({:?})",
+                temperature, humidity, self.variant
+            ),
+            Err(e) => format!("{:?} error: {}", self.variant, e),
+        }
+    }
+
+    fn http_endpoint(&self) -> Option<&'static str> {
+        Some("send_temperature_humidity")
+    }
+
+    fn values_meta(&self) -> &'static [ValueMeta] {
+        VALUES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::hal::mock::{assert_close, MockI2c, NoDelay};
+
+    type MockSensor = AHT10Sensor<MockI2c, NoDelay>;
+
+    const STATUS_READY: u8 = 0x18;
+
+    fn sensor(variant: AhtVariant, temperature_offset: f32, humidity_offset: f32) -> MockSensor {
+        let i2c = MockI2c::new(&[&[STATUS_READY]]);
+
+        AHT10Sensor::new(i2c, NoDelay, variant, temperature_offset, humidity_offset).unwrap()
+    }
+
+    /// Measurement frame as sent by `variant`, from 20-bit raw values
+    fn frame(variant: AhtVariant, raw_humidity: u32, raw_temperature: u32) -> Vec<u8> {
+        let mut data = vec![
+            STATUS_READY,
+            (raw_humidity >> 12) as u8,
+            (raw_humidity >> 4) as u8,
+            ((raw_humidity << 4) as u8) | ((raw_temperature >> 16) as u8 & 0x0F),
+            (raw_temperature >> 8) as u8,
+            raw_temperature as u8,
+        ];
+
+        if variant == AhtVariant::AHT20 {
+            data.push(MockSensor::crc8(&data));
+        }
+
+        data
+    }
+
+    fn measure(sensor: &mut MockSensor, frame: &[u8]) -> anyhow::Result<(f32, f32)> {
+        sensor.i2c.respond(&[STATUS_READY]);
+        sensor.i2c.respond(frame);
+        sensor.read_values()
+    }
+
+    #[test]
+    fn crc8_known_vector() {
+        assert_eq!(MockSensor::crc8(&[]), 0xFF);
+        assert_eq!(MockSensor::crc8(&[0xBE, 0xEF]), 0x92);
+    }
+
+    #[test]
+    fn calibrated_sensor_is_only_reset() {
+        let sensor = sensor(AhtVariant::AHT10, 0.0, 0.0);
+
+        assert_eq!(sensor.i2c.writes, vec![CMD_SOFT_RESET.to_vec()]);
+    }
+
+    #[test]
+    fn uncalibrated_sensor_is_initialised() {
+        for (variant, cmd_init) in [
+            (AhtVariant::AHT10, CMD_INIT_AHT10),
+            (AhtVariant::AHT20, CMD_INIT_AHT20),
+        ] {
+            let i2c = MockI2c::new(&[&[0x00], &[STATUS_READY]]);
+            let sensor = AHT10Sensor::new(i2c, NoDelay, variant, 0.0, 0.0).unwrap();
+
+            assert_eq!(
+                sensor.i2c.writes,
+                vec![CMD_SOFT_RESET.to_vec(), cmd_init.to_vec()]
+            );
+        }
+    }
+
+    #[test]
+    fn calibration_failure() {
+        let i2c = MockI2c::new(&[&[0x00], &[0x00]]);
+
+        assert!(AHT10Sensor::new(i2c, NoDelay, AhtVariant::AHT10, 0.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn converts_raw_values() {
+        for variant in [AhtVariant::AHT10, AhtVariant::AHT20] {
+            let mut sensor = sensor(variant, 0.0, 0.0);
+
+            // 2^19 is half the humidity range, 0.375 * 2^20 is 25 °C
+            let (temperature, humidity) =
+                measure(&mut sensor, &frame(variant, 0x80000, 0x60000)).unwrap();
+            assert_close(temperature, 25.0);
+            assert_close(humidity, 50.0);
+            assert_eq!(sensor.i2c.writes.last().unwrap(), &CMD_TRIGGER.to_vec());
+
+            let (temperature, humidity) =
+                measure(&mut sensor, &frame(variant, 0xFFFFF, 0x00000)).unwrap();
+            assert_close(temperature, -50.0);
+            assert_close(humidity, 100.0 * 0xFFFFF as f32 / 1_048_576.0);
+        }
+    }
+
+    #[test]
+    fn applies_offsets_and_clamps_humidity() {
+        let mut wet = sensor(AhtVariant::AHT10, -1.5, 60.0);
+        let (temperature, humidity) =
+            measure(&mut wet, &frame(AhtVariant::AHT10, 0x80000, 0x60000)).unwrap();
+        assert_close(temperature, 23.5);
+        assert_close(humidity, 100.0);
+
+        let mut dry = sensor(AhtVariant::AHT10, 0.0, -60.0);
+        let (_, humidity) = measure(&mut dry, &frame(AhtVariant::AHT10, 0x80000, 0x60000)).unwrap();
+        assert_close(humidity, 0.0);
+    }
+
+    #[test]
+    fn rejects_crc_mismatch() {
+        let mut sensor = sensor(AhtVariant::AHT20, 0.0, 0.0);
+        let mut data = frame(AhtVariant::AHT20, 0x80000, 0x60000);
+        data[6] ^= 0x01;
+
+        let err = measure(&mut sensor, &data).unwrap_err();
+        assert_eq!(err.to_string(), "AHT measurement CRC mismatch");
+    }
+
+    #[test]
+    fn busy_timeout() {
+        let mut sensor = sensor(AhtVariant::AHT10, 0.0, 0.0);
+        for _ in 0..MAX_BUSY_POLL {
+            sensor.i2c.respond(&[STATUS_READY | STATUS_BUSY]);
+        }
+
+        let err = sensor.read_values().unwrap_err();
+        assert_eq!(err.to_string(), "AHT sensor still busy");
+    }
+
+    #[test]
+    fn busy_frame_is_rejected() {
+        let mut sensor = sensor(AhtVariant::AHT10, 0.0, 0.0);
+        let mut data = frame(AhtVariant::AHT10, 0x80000, 0x60000);
+        data[0] |= STATUS_BUSY;
+
+        let err = measure(&mut sensor, &data).unwrap_err();
+        assert_eq!(err.to_string(), "AHT measurement not ready");
+    }
+}
@@ -0,0 +1,96 @@
+use serde_json::json;
+
+use super::{
+    hal::AdcChannel,
+    sensor::{average_mv, linear_level, Sensor, ValueMeta},
+};
+
+const MIN_BAT_VOLT: f32 = 3.2;
+const MAX_BAT_VOLT: f32 = 4.2;
+
+/// Values added to the payload
+pub const VALUES: &[ValueMeta] = &[ValueMeta {
+    key: "battery",
+    name: "Battery",
+    unit: Some("%"),
+    device_class: Some("battery"),
+}];
+
+pub struct BatterySensor<A: AdcChannel> {
+    channel: A,
+}
+
+impl<A: AdcChannel> BatterySensor<A> {
+    pub fn new(channel: A) -> Self {
+        Self { channel }
+    }
+
+    pub fn read_raw_value(&mut self, nb_sample: u8) -> u16 {
+        average_mv(&mut self.channel, nb_sample)
+    }
+
+    /// Battery voltage, the ADC sees it through a /2 divider
+    pub fn get_voltage(&mut self, nb_sample: u8) -> f32 {
+        self.read_raw_value(nb_sample) as f32 / 1000.0 * 2.0
+    }
+
+    pub fn get_level(&mut self) -> f32 {
+        linear_level(self.get_voltage(10), MIN_BAT_VOLT, MAX_BAT_VOLT)
+    }
+}
+
+impl<A: AdcChannel> Sensor for BatterySensor<A> {
+    fn add_json_value(&mut self, map: &mut serde_json::Map<String, serde_json::Value>) {
+        map.insert("battery".to_string(), json!(self.get_level()));
+    }
+
+    fn pretty_print(&mut self) -> String {
+        format!(
+            "Battery level: {}% (voltage: {} V)",
+            self.get_level(),
+            self.get_voltage(5)
+        )
+    }
+
+    fn values_meta(&self) -> &'static [ValueMeta] {
+        VALUES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::hal::mock::{assert_close, MockAdc};
+
+    #[test]
+    fn voltage_through_divider() {
+        let mut sensor = BatterySensor::new(MockAdc::constant(1850));
+
+        assert_close(sensor.get_voltage(5), 3.7);
+    }
+
+    #[test]
+    fn level_scaling() {
+        assert_close(
+            BatterySensor::new(MockAdc::constant(1850)).get_level(),
+            50.0,
+        );
+        assert_close(BatterySensor::new(MockAdc::constant(1500)).get_level(), 0.0);
+        assert_close(
+            BatterySensor::new(MockAdc::constant(2200)).get_level(),
+            100.0,
+        );
+    }
+
+    #[test]
+    fn averaged_reads() {
+        let mut sensor = BatterySensor::new(MockAdc::new(&[Some(1800), Some(1900)]));
+        assert_eq!(sensor.read_raw_value(10), 1850);
+
+        // Failed reads count as 0 mV
+        let mut sensor = BatterySensor::new(MockAdc::new(&[Some(2000), None]));
+        assert_eq!(sensor.read_raw_value(2), 1000);
+
+        assert_eq!(sensor.read_raw_value(0), 0);
+    }
+}
@@ -0,0 +1,177 @@
+/// A single ADC input returning calibrated millivolts.
+pub trait AdcChannel {
+    fn read_mv(&mut self) -> Option<u16>;
+}
+
+/// Monotonic clock with microsecond resolution.
+pub trait Clock {
+    fn now_us(&self) -> u64;
+}
+
+/// Fake hardware for the sensor tests.
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::cell::{Cell, RefCell};
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::ops::Range;
+    use std::rc::Rc;
+
+    use embedded_hal::{
+        delay::DelayNs,
+        digital::{ErrorType, InputPin, OutputPin},
+        i2c::{self, I2c, Operation},
+    };
+
+    use super::{AdcChannel, Clock};
+
+    /// ADC returning `values` in turn, `None` being a failed read.
+    pub struct MockAdc {
+        values: Vec<Option<u16>>,
+        next: usize,
+    }
+
+    impl MockAdc {
+        pub fn new(values: &[Option<u16>]) -> Self {
+            Self {
+                values: values.to_vec(),
+                next: 0,
+            }
+        }
+
+        pub fn constant(mv: u16) -> Self {
+            Self::new(&[Some(mv)])
+        }
+    }
+
+    impl AdcChannel for MockAdc {
+        fn read_mv(&mut self) -> Option<u16> {
+            let value = self.values[self.next % self.values.len()];
+            self.next += 1;
+            value
+        }
+    }
+
+    /// Output pin recording every level it is set to.
+    #[derive(Clone, Default)]
+    pub struct MockPin {
+        pub levels: Rc<RefCell<Vec<bool>>>,
+    }
+
+    impl ErrorType for MockPin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for MockPin {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.levels.borrow_mut().push(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.levels.borrow_mut().push(true);
+            Ok(())
+        }
+    }
+
+    /// Clock moving forward by `step_us` every time it is read.
+    #[derive(Clone)]
+    pub struct SteppingClock {
+        pub now_us: Rc<Cell<u64>>,
+        step_us: u64,
+    }
+
+    impl SteppingClock {
+        pub fn new(step_us: u64) -> Self {
+            Self {
+                now_us: Rc::new(Cell::new(0)),
+                step_us,
+            }
+        }
+    }
+
+    impl Clock for SteppingClock {
+        fn now_us(&self) -> u64 {
+            let now = self.now_us.get();
+            self.now_us.set(now + self.step_us);
+            now
+        }
+    }
+
+    /// Input pin high while the shared clock is within `high_us`.
+    pub struct MockEcho {
+        pub now_us: Rc<Cell<u64>>,
+        pub high_us: Range<u64>,
+    }
+
+    impl ErrorType for MockEcho {
+        type Error = Infallible;
+    }
+
+    impl InputPin for MockEcho {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.high_us.contains(&self.now_us.get()))
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            self.is_high().map(|high| !high)
+        }
+    }
+
+    /// I2C bus answering reads with `responses` in turn and recording writes.
+    pub struct MockI2c {
+        responses: VecDeque<Vec<u8>>,
+        pub writes: Vec<Vec<u8>>,
+    }
+
+    impl MockI2c {
+        pub fn new(responses: &[&[u8]]) -> Self {
+            Self {
+                responses: responses.iter().map(|r| r.to_vec()).collect(),
+                writes: Vec::new(),
+            }
+        }
+
+        pub fn respond(&mut self, response: &[u8]) {
+            self.responses.push_back(response.to_vec());
+        }
+    }
+
+    impl i2c::ErrorType for MockI2c {
+        type Error = Infallible;
+    }
+
+    impl I2c for MockI2c {
+        fn transaction(
+            &mut self,
+            _address: u8,
+            operations: &mut [Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => self.writes.push(bytes.to_vec()),
+                    Operation::Read(buffer) => {
+                        let response = self.responses.pop_front().expect("unexpected I2C read");
+                        buffer.copy_from_slice(&response);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    pub struct NoDelay;
+
+    impl DelayNs for NoDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    pub fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{InputPin, OutputPin},
+};
+use serde_json::json;
+
+use crate::string_error::StringError;
+
+use super::{
+    hal::Clock,
+    sensor::{linear_level, Sensor, ValueMeta},
+};
+
+const HALF_SPEED_SOUND: f32 = 170.0;
+const ECHO_TIMEOUT_US: u64 = 60_000;
+
+/// Values added to the payload
+pub const VALUES: &[ValueMeta] = &[
+    ValueMeta {
+        key: "level",
+        name: "Water level",
+        unit: Some("%"),
+        device_class: None,
+    },
+    ValueMeta {
+        key: "measure",
+        name: "Water distance",
+        unit: Some("mm"),
+        device_class: Some("distance"),
+    },
+];
+
+pub struct HCSR04Sensor<PEN: OutputPin, PTRIG: OutputPin, PECHO: InputPin, D: DelayNs, C: Clock> {
+    pin_enable: PEN,
+    pin_trigger: PTRIG,
+    pin_echo: PECHO,
+    delay: D,
+    clock: C,
+
+    dist_low: f32,
+    dist_high: f32,
+}
+
+impl<PEN: OutputPin, PTRIG: OutputPin, PECHO: InputPin, D: DelayNs, C: Clock>
+    HCSR04Sensor<PEN, PTRIG, PECHO, D, C>
+{
+    pub fn new(
+        pin_enable: PEN,
+        pin_trigger: PTRIG,
+        pin_echo: PECHO,
+        delay: D,
+        clock: C,
+        dist_low: f32,
+        dist_high: f32,
+    ) -> anyhow::Result<Self> {
+        let mut s = Self {
+            pin_enable,
+            pin_trigger,
+            pin_echo,
+            delay,
+            clock,
+
+            dist_low,
+            dist_high,
+        };
+
+        s.pin_enable
+            .set_low()
+            .map_err(|_| StringError("Failed to set HC-SR04 enable pin"))?;
+        s.pin_trigger
+            .set_low()
+            .map_err(|_| StringError("Failed to set HC-SR04 trigger pin"))?;
+
+        Ok(s)
+    }
+
+    pub fn read_raw_value(&mut self) -> u64 {
+        let _ = self.pin_enable.set_high();
+        self.delay.delay_ms(500);
+
+        let _ = self.pin_trigger.set_high();
+        self.delay.delay_us(5);
+        let _ = self.pin_trigger.set_low();
+
+        let result = self.measure_echo_pulse();
+
+        let _ = self.pin_enable.set_low();
+
+        result
+    }
+
+    fn measure_echo_pulse(&mut self) -> u64 {
+        let start_listen_echo = self.clock.now_us();
+        let mut start_echo_high: Option<u64> = None;
+
+        loop {
+            let now = self.clock.now_us();
+
+            if start_echo_high.is_none() && self.pin_echo.is_high().unwrap_or(false) {
+                start_echo_high = Some(now);
+            }
+
+            if let Some(start) = start_echo_high {
+                if self.pin_echo.is_low().unwrap_or(false) {
+                    return now - start;
+                }
+            }
+
+            if now - start_listen_echo > ECHO_TIMEOUT_US {
+                log::info!("Timeout !");
+                return 0;
+            }
+        }
+    }
+
+    pub fn get_distance_mm(&mut self) -> f32 {
+        let pulse_us = self.read_raw_value() as f32;
+
+        ((pulse_us / 1_000_000.0) * HALF_SPEED_SOUND) * 1_000.0
+    }
+
+    pub fn get_level(&mut self) -> f32 {
+        let dist_mm = self.get_distance_mm();
+
+        linear_level(dist_mm, self.dist_low, self.dist_high)
+    }
+}
+
+impl<PEN: OutputPin, PTRIG: OutputPin, PECHO: InputPin, D: DelayNs, C: Clock> Sensor
+    for HCSR04Sensor<PEN, PTRIG, PECHO, D, C>
+{
+    fn add_json_value(&mut self, map: &mut serde_json::Map<String, serde_json::Value>) {
+        map.insert("level".to_string(), json!(self.get_level()));
+        map.insert("measure".to_string(), json!(self.get_distance_mm()));
+    }
+
+    fn pretty_print(&mut self) -> String {
+        format!(
+            "Water level: {}% (measure: {} mm)",
+            self.get_level(),
+            self.get_distance_mm()
+        )
+    }
+
+    fn http_endpoint(&self) -> Option<&'static str> {
+        Some("send_water_level")
+    }
+
+    fn values_meta(&self) -> &'static [ValueMeta] {
+        VALUES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+    use crate::sensors::hal::mock::{assert_close, MockEcho, MockPin, NoDelay, SteppingClock};
+
+    type MockSensor = HCSR04Sensor<MockPin, MockPin, MockEcho, NoDelay, SteppingClock>;
+
+    fn sensor(step_us: u64, echo_us: Range<u64>) -> MockSensor {
+        let clock = SteppingClock::new(step_us);
+        let echo = MockEcho {
+            now_us: clock.now_us.clone(),
+            high_us: echo_us,
+        };
+
+        HCSR04Sensor::new(
+            MockPin::default(),
+            MockPin::default(),
+            echo,
+            NoDelay,
+            clock,
+            1020.0,
+            20.0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn echo_pulse_timing() {
+        assert_eq!(sensor(10, 100..1100).measure_echo_pulse(), 1000);
+        assert_eq!(sensor(1, 50..2050).measure_echo_pulse(), 2000);
+    }
+
+    #[test]
+    fn echo_timeout() {
+        // No echo at all
+        assert_eq!(sensor(100, 0..0).measure_echo_pulse(), 0);
+        // Echo never ending
+        assert_eq!(sensor(100, 1000..u64::MAX).measure_echo_pulse(), 0);
+    }
+
+    #[test]
+    fn distance_and_level() {
+        // 1 ms round trip is 170 mm
+        assert_close(sensor(10, 100..1100).get_distance_mm(), 170.0);
+        assert_close(sensor(10, 100..1100).get_level(), 85.0);
+        assert_close(sensor(10, 100..6100).get_level(), 0.0);
+    }
+}
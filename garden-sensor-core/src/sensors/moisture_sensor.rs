@@ -68,90 +68,74 @@
 // {
 // }
 
-use std::borrow::Borrow;
-
-use esp_idf_svc::hal::{
-    adc::{
-        attenuation,
-        oneshot::{config::AdcChannelConfig, AdcChannelDriver, AdcDriver},
-        Adc,
-    },
-    delay::FreeRtos,
-    gpio::{ADCPin, Output, OutputPin, PinDriver},
-};
+use embedded_hal::{delay::DelayNs, digital::OutputPin};
 use serde_json::json;
 
-use super::sensor::Sensor;
-
-pub struct MoistureSensor<
-    'a,
-    ADC: Adc + 'a,
-    PEN: OutputPin,
-    APin: ADCPin<Adc = ADC>,
-    M: Borrow<AdcDriver<'a, ADC>>,
-> {
-    channel: AdcChannelDriver<'a, APin, M>,
-    pin_enable: PinDriver<'a, PEN, Output>,
+use crate::string_error::StringError;
+
+use super::{
+    hal::AdcChannel,
+    sensor::{average_mv, linear_level, Sensor, ValueMeta},
+};
+
+/// Values added to the payload
+pub const VALUES: &[ValueMeta] = &[ValueMeta {
+    key: "level",
+    name: "Soil moisture",
+    unit: Some("%"),
+    device_class: Some("moisture"),
+}];
+
+pub struct MoistureSensor<A: AdcChannel, PEN: OutputPin, D: DelayNs> {
+    channel: A,
+    pin_enable: PEN,
+    delay: D,
     v_high: f32,
     v_low: f32,
 }
 
-impl<'a, ADC: Adc + 'a, PEN: OutputPin, APin: ADCPin<Adc = ADC>, M: Borrow<AdcDriver<'a, ADC>>>
-    MoistureSensor<'a, ADC, PEN, APin, M>
-{
+impl<A: AdcChannel, PEN: OutputPin, D: DelayNs> MoistureSensor<A, PEN, D> {
     pub fn new(
-        adc_driver: M,
-        pin_adc: APin,
+        channel: A,
         pin_enable: PEN,
+        delay: D,
         voltage_high: f32,
         voltage_low: f32,
     ) -> anyhow::Result<Self> {
         let mut s = Self {
-            channel: AdcChannelDriver::new(
-                adc_driver,
-                pin_adc,
-                &AdcChannelConfig {
-                    attenuation: attenuation::DB_11,
-                    calibration: true,
-                    ..Default::default()
-                },
-            )?,
-            pin_enable: PinDriver::output(pin_enable)?,
+            channel,
+            pin_enable,
+            delay,
             v_high: voltage_high,
             v_low: voltage_low,
         };
 
-        s.pin_enable.set_low()?;
+        s.pin_enable
+            .set_low()
+            .map_err(|_| StringError("Failed to set moisture enable pin"))?;
 
         Ok(s)
     }
 
     pub fn read_raw_value(&mut self, nb_sample: u8) -> u16 {
         let _ = self.pin_enable.set_high();
-        FreeRtos::delay_ms(100);
-
-        let mut result = 0;
+        self.delay.delay_ms(100);
 
-        for _ in 0..nb_sample {
-            result += self.channel.read().unwrap_or(0);
-        }
+        let result = average_mv(&mut self.channel, nb_sample);
 
         let _ = self.pin_enable.set_low();
-        FreeRtos::delay_ms(100);
+        self.delay.delay_ms(100);
 
-        result / nb_sample as u16
+        result
     }
 
     pub fn get_level(&mut self) -> f32 {
         let adc_value = self.read_raw_value(10) as f32 / 1000.0;
-        let slope: f32 = 100.0 / (self.v_high - self.v_low);
-        (slope * (adc_value - self.v_low)).clamp(0.0, 100.0)
+        linear_level(adc_value, self.v_low, self.v_high)
     }
 }
 
-impl<'a, ADC: Adc + 'a, PEN: OutputPin, APin: ADCPin<Adc = ADC>, M: Borrow<AdcDriver<'a, ADC>>>
-    Sensor for MoistureSensor<'a, ADC, PEN, APin, M>
-{
+impl<A: AdcChannel, PEN: OutputPin, D: DelayNs> Sensor for MoistureSensor<A, PEN, D> {
     fn add_json_value(&mut self, map: &mut serde_json::Map<String, serde_json::Value>) {
         map.insert("level".to_string(), json!(self.get_level()));
     }
@@ -163,4 +147,55 @@ impl<'a, ADC: Adc + 'a, PEN: OutputPin, APin: ADCPin<Adc = ADC>, M: Borrow<AdcDr
             self.read_raw_value(5)
         )
     }
+
+    fn http_endpoint(&self) -> Option<&'static str> {
+        Some("send_soil_moisture")
+    }
+
+    fn values_meta(&self) -> &'static [ValueMeta] {
+        VALUES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::hal::mock::{assert_close, MockAdc, MockPin, NoDelay};
+
+    fn sensor(adc: MockAdc, pin_enable: MockPin) -> MoistureSensor<MockAdc, MockPin, NoDelay> {
+        MoistureSensor::new(adc, pin_enable, NoDelay, 2.55, 1.26).unwrap()
+    }
+
+    #[test]
+    fn level_scaling() {
+        assert_close(
+            sensor(MockAdc::constant(1905), MockPin::default()).get_level(),
+            50.0,
+        );
+        assert_close(
+            sensor(MockAdc::constant(1260), MockPin::default()).get_level(),
+            0.0,
+        );
+        assert_close(
+            sensor(MockAdc::constant(3000), MockPin::default()).get_level(),
+            100.0,
+        );
+    }
+
+    #[test]
+    fn averaged_reads() {
+        let adc = MockAdc::new(&[Some(1000), Some(2000), Some(1500)]);
+
+        assert_eq!(sensor(adc, MockPin::default()).read_raw_value(3), 1500);
+    }
+
+    #[test]
+    fn probe_powered_only_while_reading() {
+        let pin_enable = MockPin::default();
+        let mut sensor = sensor(MockAdc::constant(1500), pin_enable.clone());
+        assert_eq!(*pin_enable.levels.borrow(), [false]);
+
+        sensor.read_raw_value(1);
+        assert_eq!(*pin_enable.levels.borrow(), [false, true, false]);
+    }
 }
@@ -0,0 +1,155 @@
+use serde_json::{Map, Value};
+
+use super::hal::AdcChannel;
+
+pub type SensorsVec = Vec<Box<dyn Sensor + Send>>;
+
+/// Description of one value written by `Sensor::add_json_value`, used to
+/// announce it to Home Assistant.
+pub struct ValueMeta {
+    /// JSON key of the value in the payload
+    pub key: &'static str,
+    pub name: &'static str,
+    pub unit: Option<&'static str>,
+    /// Home Assistant sensor device class
+    pub device_class: Option<&'static str>,
+}
+
+pub trait Sensor {
+    fn add_json_value(&mut self, map: &mut Map<String, Value>);
+    fn pretty_print(&mut self) -> String;
+
+    /// Server endpoint this sensor reports to, or `None` if its values are
+    /// shared by every payload (e.g. battery).
+    fn http_endpoint(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Values this sensor adds to the payload.
+    fn values_meta(&self) -> &'static [ValueMeta] {
+        &[]
+    }
+}
+
+/// Endpoints of `sensors`, each once, in the order of their first sensor.
+/// One payload is sent to each.
+pub fn endpoints(sensors: &SensorsVec) -> Vec<&'static str> {
+    let mut endpoints = Vec::new();
+
+    for endpoint in sensors.iter().filter_map(|s| s.http_endpoint()) {
+        if !endpoints.contains(&endpoint) {
+            endpoints.push(endpoint);
+        }
+    }
+
+    endpoints
+}
+
+/// Map `value` linearly so that `low` is 0% and `high` is 100%, 0% when both
+/// bounds are equal.
+pub fn linear_level(value: f32, low: f32, high: f32) -> f32 {
+    if high == low {
+        return 0.0;
+    }
+
+    let slope = 100.0 / (high - low);
+    (slope * (value - low)).clamp(0.0, 100.0)
+}
+
+/// Average of `nb_sample` ADC reads, failed reads count as 0 mV.
+pub fn average_mv<A: AdcChannel>(channel: &mut A, nb_sample: u8) -> u16 {
+    if nb_sample == 0 {
+        return 0;
+    }
+
+    let mut result: u32 = 0;
+    for _ in 0..nb_sample {
+        result += channel.read_mv().unwrap_or(0) as u32;
+    }
+
+    (result / nb_sample as u32) as u16
+}
+
+/// Sensor reporting fixed values, for the tests of the code handling sensors.
+#[cfg(test)]
+pub(crate) mod mock {
+    use serde_json::{json, Map, Value};
+
+    use super::Sensor;
+
+    pub struct FakeSensor {
+        pub endpoint: Option<&'static str>,
+        pub key: &'static str,
+        pub value: f32,
+    }
+
+    impl Sensor for FakeSensor {
+        fn add_json_value(&mut self, map: &mut Map<String, Value>) {
+            map.insert(self.key.to_string(), json!(self.value));
+        }
+
+        fn pretty_print(&mut self) -> String {
+            format!("{}: {}", self.key, self.value)
+        }
+
+        fn http_endpoint(&self) -> Option<&'static str> {
+            self.endpoint
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensors::hal::mock::MockAdc;
+    use crate::sensors::sensor::mock::FakeSensor;
+
+    #[test]
+    fn endpoints_once_in_sensor_order() {
+        let sensors: SensorsVec = [None, Some("b"), Some("a"), None, Some("b")]
+            .into_iter()
+            .map(|endpoint| {
+                Box::new(FakeSensor {
+                    endpoint,
+                    key: "level",
+                    value: 0.0,
+                }) as Box<dyn Sensor + Send>
+            })
+            .collect();
+
+        assert_eq!(endpoints(&sensors), ["b", "a"]);
+    }
+
+    #[test]
+    fn linear_level_bounds() {
+        assert_eq!(linear_level(2.5, 2.0, 3.0), 50.0);
+        assert_eq!(linear_level(1.0, 2.0, 3.0), 0.0);
+        assert_eq!(linear_level(4.0, 2.0, 3.0), 100.0);
+    }
+
+    #[test]
+    fn linear_level_reversed_bounds() {
+        // A water distance decreases as the level rises
+        assert_eq!(linear_level(270.0, 1020.0, 20.0), 75.0);
+        assert_eq!(linear_level(10.0, 1020.0, 20.0), 100.0);
+    }
+
+    #[test]
+    fn linear_level_equal_bounds() {
+        assert_eq!(linear_level(1.5, 1.5, 1.5), 0.0);
+    }
+
+    #[test]
+    fn average_of_reads() {
+        assert_eq!(
+            average_mv(&mut MockAdc::new(&[Some(100), Some(300)]), 4),
+            200
+        );
+        assert_eq!(
+            average_mv(&mut MockAdc::new(&[Some(300), None, None]), 3),
+            100
+        );
+        assert_eq!(average_mv(&mut MockAdc::constant(3300), 255), 3300);
+        assert_eq!(average_mv(&mut MockAdc::constant(3300), 0), 0);
+    }
+}
@@ -0,0 +1,25 @@
+use core::fmt;
+
+use embedded_hal::i2c::ErrorKind as I2cErrorKind;
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct StringError(pub &'static str);
+
+impl std::error::Error for StringError {}
+
+impl fmt::Display for StringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct StringI2cError(pub &'static str, pub I2cErrorKind);
+
+impl std::error::Error for StringI2cError {}
+
+impl fmt::Display for StringI2cError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (I2C error: {})", self.0, self.1)
+    }
+}
@@ -0,0 +1,282 @@
+use crate::configuration::config_store::ConfigStore;
+use crate::configuration::main_configuration;
+use crate::configuration::nvs_configuration::NvsConfiguration;
+use crate::sensors::sensor::SensorsVec;
+
+const BASE_HTML: &str = include_str!("html/base.html");
+
+const SENSOR_FORM_HTML: &[&str] = &[
+    #[cfg(feature = "moisture-sensor")]
+    include_str!("html/form_moisture.html"),
+    #[cfg(feature = "water-level-sensor")]
+    include_str!("html/form_water_level.html"),
+    #[cfg(feature = "aht10-sensor")]
+    include_str!("html/form_aht10.html"),
+];
+
+/// Wi-Fi network found by a scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessPoint {
+    pub ssid: String,
+    pub rssi: i8,
+}
+
+pub fn to_html<S: ConfigStore>(
+    main_config: &NvsConfiguration<S>,
+    error_message: Option<String>,
+    aps: Option<Vec<AccessPoint>>,
+    sensor_value: &str,
+) -> String {
+    generate_html(
+        main_config,
+        error_message,
+        aps,
+        sensor_value,
+        &SENSOR_FORM_HTML.join("\n"),
+    )
+}
+
+pub fn generate_html<S: ConfigStore>(
+    main_config: &NvsConfiguration<S>,
+    error_message: Option<String>,
+    aps: Option<Vec<AccessPoint>>,
+    sensor_value: &str,
+    form_setting: &str,
+) -> String {
+    let mut template = BASE_HTML.to_string();
+
+    template = template.replace("{FORM_SETTINGS}", form_setting);
+    template = template.replace(
+        "{ERROR_MSG}",
+        &js_escape(&error_message.unwrap_or("".to_string())),
+    );
+    template = template.replace("{AP_LIST}", &accespoint_to_template(aps));
+    template = template.replace("{SSID_JS}", &js_escape(&main_config.get_ssid()));
+    template = template.replace("{SENSOR_VALUE}", &html_escape(sensor_value));
+    template = template.replace(
+        "{CA_CERT_STATUS}",
+        &match main_config.get_ca_certificate() {
+            Some(cert) => format!("stored ({} bytes)", cert.len() - 1),
+            None => "none".to_string(),
+        },
+    );
+
+    // Stored values are placed in HTML attributes, or in scripts for numbers
+    // only. Text may come from the update server, so it is escaped.
+    for elem in main_configuration::MAP_NVS_FORM {
+        if let Some(template_id) = elem.template_id {
+            template = template.replace(
+                template_id,
+                &html_escape(&main_configuration::read_form_value(main_config, elem).to_string()),
+            );
+        }
+    }
+
+    template
+}
+
+pub fn generate_html_value(sensors: &mut SensorsVec) -> String {
+    let mut result = String::new();
+
+    for sensor in sensors {
+        result += &sensor.pretty_print();
+        result += "\n";
+    }
+
+    result
+}
+
+fn accespoint_to_template(aps: Option<Vec<AccessPoint>>) -> String {
+    let mut result = String::new();
+
+    result += "[";
+    if let Some(aps) = aps {
+        for ap in aps {
+            result += &format!("{{ssid:\"{}\",rssi:{}}},", js_escape(&ap.ssid), ap.rssi);
+        }
+    }
+    result += "]";
+
+    result
+}
+
+/// `text` for HTML content or a quoted attribute value
+fn html_escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => result += "&amp;",
+            '<' => result += "&lt;",
+            '>' => result += "&gt;",
+            '"' => result += "&quot;",
+            '\'' => result += "&#39;",
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// `text` for a quoted JavaScript string in a `<script>` element: besides
+/// quotes, backslashes and line breaks, `<`, `>` and `&` are escaped so the
+/// text cannot close the element.
+fn js_escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_control()
+            || matches!(
+                c,
+                '"' | '\'' | '\\' | '<' | '>' | '&' | '\u{2028}' | '\u{2029}'
+            )
+        {
+            result += &format!("\\u{:04x}", c as u32);
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::main_configuration::{apply_form, apply_form_body, MAP_NVS_FORM};
+    use crate::configuration::nvs_configuration::mock::config;
+    use crate::configuration::nvs_configuration::{KEY_NAME, KEY_SSID};
+
+    #[test]
+    fn every_placeholder_filled() {
+        let html = to_html(&config(), None, None, "");
+
+        for elem in MAP_NVS_FORM {
+            if let Some(template_id) = elem.template_id {
+                assert!(!html.contains(template_id), "{}", template_id);
+            }
+        }
+        assert!(!html.contains("{FORM_SETTINGS}"));
+        assert!(!html.contains("{SSID_JS}"));
+        assert!(!html.contains("{CA_CERT_STATUS}"));
+    }
+
+    #[test]
+    fn stored_values_shown() {
+        let mut config = config();
+        apply_form(&mut config, "ssid=Home&name=Tomatoes&pass=hunter22").unwrap();
+
+        let html = generate_html(
+            &config,
+            Some("Save successfully!".to_string()),
+            Some(vec![AccessPoint {
+                ssid: "Neighbour".to_string(),
+                rssi: -70,
+            }]),
+            "Battery level: 50%",
+            "<input value=\"{NAME}\"/>",
+        );
+
+        assert!(html.contains("<input value=\"Tomatoes\"/>"));
+        assert!(html.contains("Home"));
+        assert!(html.contains("Save successfully!"));
+        assert!(html.contains("{ssid:\"Neighbour\",rssi:-70},"));
+        assert!(html.contains("Battery level: 50%"));
+        assert!(!html.contains("hunter22"));
+    }
+
+    #[test]
+    fn html_escaping() {
+        assert_eq!(
+            html_escape("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+        assert_eq!(html_escape("Café 50%"), "Café 50%");
+    }
+
+    #[test]
+    fn js_escaping() {
+        assert_eq!(
+            js_escape("\"</script>\\'"),
+            "\\u0022\\u003c/script\\u003e\\u005c\\u0027"
+        );
+        assert_eq!(js_escape("a\nb\u{2028}&"), "a\\u000ab\\u2028\\u0026");
+        assert_eq!(js_escape("Café 50%"), "Café 50%");
+    }
+
+    #[test]
+    fn untrusted_text_escaped() {
+        let mut config = config();
+        config.store_string(KEY_NAME, "\"><script>", 32).unwrap();
+        config
+            .store_string(KEY_SSID, "Home\"</script>", 32)
+            .unwrap();
+
+        let html = generate_html(
+            &config,
+            Some("Bad \"value\"\n".to_string()),
+            Some(vec![AccessPoint {
+                ssid: "\"});alert(1)//</script>".to_string(),
+                rssi: -70,
+            }]),
+            "<b>Sensor</b>",
+            "<input value=\"{NAME}\"/>",
+        );
+
+        assert!(html.contains("<input value=\"&quot;&gt;&lt;script&gt;\"/>"));
+        assert!(html.contains("value=\"Home&quot;&lt;/script&gt;\""));
+        assert!(html.contains("\"Home\\u0022\\u003c/script\\u003e\");"));
+        assert!(html.contains("let e=\"Bad \\u0022value\\u0022\\u000a\";"));
+        assert!(html.contains("{ssid:\"\\u0022});alert(1)//\\u003c/script\\u003e\",rssi:-70}"));
+        assert!(html.contains("<pre>&lt;b&gt;Sensor&lt;/b&gt;</pre>"));
+        assert_eq!(
+            html.matches("</script>").count(),
+            BASE_HTML.matches("</script>").count()
+        );
+    }
+
+    #[test]
+    fn settings_submission() {
+        let mut config = config();
+
+        let message = apply_form_body(&mut config, b"name=Tomatoes&sleep=600000000");
+        let html = to_html(&config, Some(message), None, "");
+        assert!(html.contains("Save successfully!"));
+        assert!(html.contains("value=\"Tomatoes\""));
+        assert!(html.contains("value=\"600000000\""));
+
+        let message = apply_form_body(&mut config, b"name=Peppers&sleep=1");
+        assert_eq!(message, "Save error: sleep: Value out of range");
+        assert!(to_html(&config, Some(message), None, "").contains("value=\"Tomatoes\""));
+
+        assert_eq!(apply_form_body(&mut config, b""), "Save error: No body");
+        assert_eq!(
+            apply_form_body(&mut config, b"name=\xff"),
+            "Save error: Body is not UTF-8"
+        );
+    }
+
+    #[test]
+    fn ca_certificate_status() {
+        let mut config = config();
+        assert!(generate_html(&config, None, None, "", "").contains("current: none)"));
+
+        let pem = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----";
+        config.store_ca_certificate(pem).unwrap();
+        assert!(generate_html(&config, None, None, "", "")
+            .contains(&format!("current: stored ({} bytes))", pem.len())));
+    }
+
+    #[test]
+    fn sensor_values_listed() {
+        use crate::sensors::sensor::{mock::FakeSensor, SensorsVec};
+
+        let mut sensors: SensorsVec = vec![Box::new(FakeSensor {
+            endpoint: None,
+            key: "battery",
+            value: 80.0,
+        })];
+
+        assert_eq!(generate_html_value(&mut sensors), "battery: 80\n");
+    }
+}
@@ -0,0 +1,393 @@
+//! Choice of the Wi-Fi network to join among the saved ones.
+
+use std::fmt;
+
+pub use crate::configuration::nvs_configuration::WifiAuth;
+
+/// Number of Wi-Fi networks that can be saved
+pub const MAX_NETWORKS: usize = 3;
+
+/// Longest deep sleep after failed connections, unless the configured one is
+/// longer
+pub const MAX_BACKOFF_SLEEP_US: u64 = 24 * 3600 * 1_000_000;
+
+/// Saved Wi-Fi network
+#[derive(Debug, Clone, PartialEq)]
+pub struct Network {
+    pub ssid: String,
+    pub password: String,
+    pub auth: WifiAuth,
+}
+
+/// Access point found by a scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedAp {
+    pub ssid: String,
+    pub bssid: [u8; 6],
+    pub channel: u8,
+    pub rssi: i8,
+    /// `None` for a security the device does not support (e.g. enterprise)
+    pub auth: Option<WifiAuth>,
+}
+
+/// Access point a connection succeeded with, joined directly at the next
+/// connection, without scanning.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachedAp {
+    /// Index in the saved networks
+    pub network: usize,
+    pub bssid: [u8; 6],
+    pub channel: u8,
+    pub auth: WifiAuth,
+}
+
+impl CachedAp {
+    /// Candidate tried before scanning, `None` when the saved network it was
+    /// found for no longer exists.
+    pub fn candidate<'a>(&self, networks: &'a [Network]) -> Option<Candidate<'a>> {
+        Some(Candidate {
+            index: self.network,
+            network: networks.get(self.network)?,
+            bssid: Some(self.bssid),
+            channel: Some(self.channel),
+            scanned_auth: Some(self.auth),
+        })
+    }
+}
+
+/// Saved network to try, with the access point to join when the scan found
+/// it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate<'a> {
+    /// Index in the saved networks
+    pub index: usize,
+    pub network: &'a Network,
+    pub bssid: Option<[u8; 6]>,
+    pub channel: Option<u8>,
+    /// Security of the access point, when found by the scan
+    pub scanned_auth: Option<WifiAuth>,
+}
+
+impl Candidate<'_> {
+    /// Security to connect with, never [`WifiAuth::Auto`]: the selected one,
+    /// else the scanned one, else guessed from the passphrase.
+    pub fn auth(&self) -> WifiAuth {
+        match (self.network.auth, self.scanned_auth) {
+            (WifiAuth::Auto, Some(scanned)) if scanned != WifiAuth::Auto => scanned,
+            (WifiAuth::Auto, _) if self.network.password.is_empty() => WifiAuth::Open,
+            (WifiAuth::Auto, _) => WifiAuth::Wpa2,
+            (selected, _) => selected,
+        }
+    }
+
+    /// Tell why the connection cannot work, when the scan shows the access
+    /// point security does not match the settings.
+    pub fn check_auth(&self) -> Result<(), AuthError> {
+        let Some(found) = self.scanned_auth else {
+            return Ok(());
+        };
+
+        if found != WifiAuth::Open && self.network.password.is_empty() {
+            return Err(AuthError::MissingPassphrase {
+                ssid: self.network.ssid.clone(),
+                found,
+            });
+        }
+
+        if !satisfies(found, self.auth()) {
+            return Err(AuthError::Mismatch {
+                ssid: self.network.ssid.clone(),
+                found,
+                selected: self.auth(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Saved network whose access point security does not match the settings
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// The network is protected but no passphrase is saved
+    MissingPassphrase { ssid: String, found: WifiAuth },
+    /// The network security is weaker than the selected one
+    Mismatch {
+        ssid: String,
+        found: WifiAuth,
+        selected: WifiAuth,
+    },
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingPassphrase { ssid, found } => write!(
+                f,
+                "Wi-Fi '{}' is a {} network but no passphrase is saved",
+                ssid, found
+            ),
+            AuthError::Mismatch {
+                ssid,
+                found,
+                selected,
+            } => write!(
+                f,
+                "Wi-Fi '{}' is a {} network but {} is selected in the settings",
+                ssid, found, selected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// `true` when an access point with the `found` security accepts a station
+/// requiring `selected`. Transition mode access points also accept WPA3 (SAE)
+/// stations.
+fn satisfies(found: WifiAuth, selected: WifiAuth) -> bool {
+    (found == WifiAuth::Wpa2Wpa3 && selected == WifiAuth::Wpa3)
+        || strength(found) >= strength(selected)
+}
+
+/// Weakest accepted security first, [`WifiAuth::Auto`] accepts anything.
+fn strength(auth: WifiAuth) -> u8 {
+    match auth {
+        WifiAuth::Auto | WifiAuth::Open => 0,
+        WifiAuth::WpaWpa2 => 1,
+        WifiAuth::Wpa2 => 2,
+        WifiAuth::Wpa2Wpa3 => 3,
+        WifiAuth::Wpa3 => 4,
+    }
+}
+
+/// Saved networks in the order to try them: the ones seen by the scan,
+/// strongest signal first, then the others (e.g. hidden networks) in saved
+/// order.
+pub fn connection_order<'a>(networks: &'a [Network], scan: &[ScannedAp]) -> Vec<Candidate<'a>> {
+    let mut seen = Vec::new();
+    let mut unseen = Vec::new();
+
+    for (index, network) in networks.iter().enumerate() {
+        let best = scan
+            .iter()
+            .filter(|ap| ap.ssid == network.ssid)
+            .max_by_key(|ap| ap.rssi);
+
+        match best {
+            Some(ap) => seen.push((
+                ap.rssi,
+                Candidate {
+                    index,
+                    network,
+                    bssid: Some(ap.bssid),
+                    channel: Some(ap.channel),
+                    scanned_auth: ap.auth,
+                },
+            )),
+            None => unseen.push(Candidate {
+                index,
+                network,
+                bssid: None,
+                channel: None,
+                scanned_auth: None,
+            }),
+        }
+    }
+
+    // Stable sort, equal signals keep the saved order
+    seen.sort_by_key(|(rssi, _)| -(*rssi as i16));
+
+    seen.into_iter()
+        .map(|(_, candidate)| candidate)
+        .chain(unseen)
+        .collect()
+}
+
+/// Deep sleep duration after `failures` connections failed in a row: the
+/// configured duration doubled at each failure, up to
+/// [`MAX_BACKOFF_SLEEP_US`].
+pub fn backoff_sleep(base_us: u64, failures: u8) -> u64 {
+    let factor = 1u64 << failures.min(16);
+
+    base_us
+        .saturating_mul(factor)
+        .min(MAX_BACKOFF_SLEEP_US.max(base_us))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(ssid: &str, password: &str, auth: WifiAuth) -> Network {
+        Network {
+            ssid: ssid.to_string(),
+            password: password.to_string(),
+            auth,
+        }
+    }
+
+    fn candidate(network: &Network, scanned_auth: Option<WifiAuth>) -> Candidate<'_> {
+        Candidate {
+            index: 0,
+            network,
+            bssid: None,
+            channel: None,
+            scanned_auth,
+        }
+    }
+
+    #[test]
+    fn transition_mode_accepts_wpa2_and_wpa3() {
+        for selected in [WifiAuth::Wpa2, WifiAuth::Wpa2Wpa3, WifiAuth::Wpa3] {
+            let network = network("Home", "passphrase", selected);
+            assert_eq!(
+                candidate(&network, Some(WifiAuth::Wpa2Wpa3)).check_auth(),
+                Ok(()),
+                "{}",
+                selected
+            );
+        }
+    }
+
+    #[test]
+    fn weaker_security_rejected() {
+        let network = network("Home", "passphrase", WifiAuth::Wpa3);
+
+        assert_eq!(
+            candidate(&network, Some(WifiAuth::Wpa2)).check_auth(),
+            Err(AuthError::Mismatch {
+                ssid: "Home".to_string(),
+                found: WifiAuth::Wpa2,
+                selected: WifiAuth::Wpa3,
+            })
+        );
+        assert!(candidate(&network, None).check_auth().is_ok());
+    }
+
+    #[test]
+    fn missing_passphrase_rejected() {
+        let network = network("Home", "", WifiAuth::Auto);
+
+        assert_eq!(
+            candidate(&network, Some(WifiAuth::Wpa2)).check_auth(),
+            Err(AuthError::MissingPassphrase {
+                ssid: "Home".to_string(),
+                found: WifiAuth::Wpa2,
+            })
+        );
+        assert_eq!(candidate(&network, None).auth(), WifiAuth::Open);
+    }
+
+    fn scanned(ssid: &str, bssid: u8, rssi: i8) -> ScannedAp {
+        ScannedAp {
+            ssid: ssid.to_string(),
+            bssid: [bssid; 6],
+            channel: bssid,
+            rssi,
+            auth: Some(WifiAuth::Wpa2),
+        }
+    }
+
+    fn order(networks: &[Network], scan: &[ScannedAp]) -> Vec<(usize, Option<[u8; 6]>)> {
+        connection_order(networks, scan)
+            .iter()
+            .map(|c| (c.index, c.bssid))
+            .collect()
+    }
+
+    #[test]
+    fn cached_ap_tried_without_scan() {
+        let networks = [
+            network("Home", "passphrase", WifiAuth::Auto),
+            network("Garden", "passphrase", WifiAuth::Auto),
+        ];
+        let cached = CachedAp {
+            network: 1,
+            bssid: [7; 6],
+            channel: 11,
+            auth: WifiAuth::Wpa2Wpa3,
+        };
+
+        assert_eq!(
+            cached.candidate(&networks),
+            Some(Candidate {
+                index: 1,
+                network: &networks[1],
+                bssid: Some([7; 6]),
+                channel: Some(11),
+                scanned_auth: Some(WifiAuth::Wpa2Wpa3),
+            })
+        );
+
+        // The network was removed from the settings since
+        assert_eq!(cached.candidate(&networks[..1]), None);
+    }
+
+    #[test]
+    fn strongest_signal_first() {
+        let networks = [
+            network("Home", "passphrase", WifiAuth::Auto),
+            network("Garden", "passphrase", WifiAuth::Auto),
+            network("Shed", "passphrase", WifiAuth::Auto),
+        ];
+        let scan = [
+            scanned("Home", 1, -80),
+            scanned("Garden", 2, -50),
+            scanned("Home", 3, -60),
+            scanned("Shed", 4, -60),
+        ];
+
+        // Best access point of each network, equal signals in saved order
+        assert_eq!(
+            order(&networks, &scan),
+            vec![(1, Some([2; 6])), (0, Some([3; 6])), (2, Some([4; 6]))]
+        );
+    }
+
+    #[test]
+    fn networks_missing_from_scan_last() {
+        let networks = [
+            network("Hidden", "passphrase", WifiAuth::Wpa2),
+            network("Home", "passphrase", WifiAuth::Auto),
+            network("Away", "passphrase", WifiAuth::Auto),
+        ];
+        let scan = [scanned("Home", 1, -70), scanned("Neighbour", 2, -40)];
+
+        assert_eq!(
+            order(&networks, &scan),
+            vec![(1, Some([1; 6])), (0, None), (2, None)]
+        );
+
+        let unseen = connection_order(&networks, &[]);
+        assert_eq!(
+            unseen.iter().map(|c| c.index).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert!(unseen
+            .iter()
+            .all(|c| c.channel.is_none() && c.scanned_auth.is_none()));
+    }
+
+    #[test]
+    fn backoff_doubles_per_failure() {
+        let base = 15 * 60 * 1_000_000;
+
+        assert_eq!(backoff_sleep(base, 0), base);
+        assert_eq!(backoff_sleep(base, 1), 2 * base);
+        assert_eq!(backoff_sleep(base, 4), 16 * base);
+        assert_eq!(backoff_sleep(base, 7), MAX_BACKOFF_SLEEP_US);
+    }
+
+    #[test]
+    fn backoff_capped() {
+        for failures in [16, 17, 64, u8::MAX] {
+            assert_eq!(backoff_sleep(60_000_000, failures), MAX_BACKOFF_SLEEP_US);
+            assert_eq!(backoff_sleep(u64::MAX, failures), u64::MAX);
+        }
+
+        // A configured sleep longer than the cap is kept
+        let long = 2 * MAX_BACKOFF_SLEEP_US;
+        assert_eq!(backoff_sleep(long, 3), long);
+    }
+}
@@ -0,0 +1,45 @@
+//! DNS server of the captive portal, see [`garden_sensor_core::captive_dns`].
+
+use std::net::{Ipv4Addr, UdpSocket};
+use std::thread;
+
+use esp_idf_svc::hal::delay::FreeRtos;
+use garden_sensor_core::captive_dns;
+use log::{info, warn};
+
+const DNS_PORT: u16 = 53;
+
+/// Largest DNS message over UDP without EDNS
+const MAX_MESSAGE_LEN: usize = 512;
+
+/// Answer every DNS query with `address`, in a background thread.
+pub fn start(address: Ipv4Addr) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DNS_PORT))?;
+
+    thread::Builder::new()
+        .name("dns".to_string())
+        .stack_size(4096)
+        .spawn(move || {
+            let mut buffer = [0u8; MAX_MESSAGE_LEN];
+
+            loop {
+                match socket.recv_from(&mut buffer) {
+                    Ok((len, peer)) => {
+                        if let Some(response) =
+                            captive_dns::response(&buffer[..len], address.octets())
+                        {
+                            let _ = socket.send_to(&response, peer);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("[DNS] Receive failed: {}", e);
+                        FreeRtos::delay_ms(100);
+                    }
+                }
+            }
+        })?;
+
+    info!("[DNS] Captive portal DNS started on {}", address);
+
+    Ok(())
+}
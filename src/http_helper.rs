@@ -0,0 +1,85 @@
+//! Request bodies of the settings portal, and the update manifest.
+
+use embedded_svc::http::Headers;
+use esp_idf_svc::hal::io::Read;
+
+use crate::string_error::StringError;
+
+/// Largest settings form or JSON body accepted, in bytes: the
+/// `MAX_SETTINGS_BODY_LEN` environment variable at build time, else 4096
+pub const MAX_SETTINGS_BODY_LEN: usize = match option_env!("MAX_SETTINGS_BODY_LEN") {
+    Some(len) => parse_len(len, DEFAULT_MAX_SETTINGS_BODY_LEN),
+    None => DEFAULT_MAX_SETTINGS_BODY_LEN,
+};
+
+const DEFAULT_MAX_SETTINGS_BODY_LEN: usize = 4096;
+
+const CHUNK_SIZE: usize = 512;
+
+/// Read a whole request body, a body may take several reads to arrive. Bodies
+/// larger than `max_len`, announced by `Content-Length` or not, are rejected
+/// before being buffered.
+pub fn read_body<R: Read + Headers>(req: &mut R, max_len: usize) -> Result<Vec<u8>, StringError> {
+    let expected = req.content_len();
+
+    if expected.is_some_and(|len| len > max_len as u64) {
+        return Err(StringError("Body too large"));
+    }
+
+    let mut body = Vec::with_capacity(expected.unwrap_or(0) as usize);
+    let mut chunk = [0u8; CHUNK_SIZE];
+
+    loop {
+        let len = req
+            .read(&mut chunk)
+            .map_err(|_| StringError("Failed to read request"))?;
+
+        if len == 0 {
+            break;
+        }
+
+        if body.len() + len > max_len {
+            return Err(StringError("Body too large"));
+        }
+
+        body.extend_from_slice(&chunk[..len]);
+
+        if expected.is_some_and(|len| body.len() as u64 >= len) {
+            break;
+        }
+    }
+
+    if expected.is_some_and(|len| (body.len() as u64) < len) {
+        return Err(StringError("Request body incomplete"));
+    }
+
+    Ok(body)
+}
+
+/// Positive decimal length, `default` if `s` is not one
+const fn parse_len(s: &str, default: usize) -> usize {
+    let digits = s.as_bytes();
+    let mut len: usize = 0;
+    let mut i = 0;
+
+    while i < digits.len() {
+        if !digits[i].is_ascii_digit() {
+            return default;
+        }
+
+        len = match len.checked_mul(10) {
+            Some(l) => match l.checked_add((digits[i] - b'0') as usize) {
+                Some(l) => l,
+                None => return default,
+            },
+            None => return default,
+        };
+        i += 1;
+    }
+
+    if len == 0 {
+        default
+    } else {
+        len
+    }
+}
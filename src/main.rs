@@ -1,5 +1,8 @@
+use std::ops::RangeInclusive;
+use std::ptr::addr_of_mut;
 use std::str::from_utf8;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::time::Duration;
 use std::time::SystemTime;
@@ -7,7 +10,10 @@ use std::time::SystemTime;
 use anyhow::Ok;
 // use board::board::Board;
 // use board::on_board_led::OnBoardLed;
-use configuration::{main_configuration, nvs_configuration::NvsConfiguration};
+use configuration::main_configuration;
+use configuration::nvs_configuration::{
+    EspConfigStore, HttpMethod, HttpTls, NvsConfiguration, Uplink, AP_PASSWORD_LEN, MAX_CA_CERT_LEN,
+};
 use embedded_svc::{
     http::client::{Client as HttpClient, Response},
     utils::io,
@@ -22,30 +28,43 @@ use esp_idf_svc::hal::gpio::Pin;
 use esp_idf_svc::hal::gpio::PinDriver;
 use esp_idf_svc::hal::io::Write;
 use esp_idf_svc::hal::peripherals::Peripherals;
-use esp_idf_svc::hal::sys::esp_deep_sleep;
+use esp_idf_svc::hal::sys::{
+    bootloader_fill_random, bootloader_random_disable, bootloader_random_enable,
+};
+use esp_idf_svc::hal::sys::{
+    esp, esp_http_client_get_and_clear_last_tls_error, esp_tls_set_global_ca_store, EspError,
+};
+use esp_idf_svc::hal::sys::{esp_deep_sleep, esp_timer_get_time};
 use esp_idf_svc::hal::task::watchdog::TWDTConfig;
 use esp_idf_svc::hal::task::watchdog::TWDTDriver;
+use esp_idf_svc::handle::RawHandle;
 use esp_idf_svc::http::client::EspHttpConnection;
 use esp_idf_svc::http::{self, server::EspHttpServer, Method};
+use esp_idf_svc::mdns::EspMdns;
 use esp_idf_svc::wifi::{BlockingWifi, EspWifi};
+use garden_sensor_core::api;
+use garden_sensor_core::auth;
+use garden_sensor_core::change_report::LastReport;
+use garden_sensor_core::home_assistant::{discovery_messages, discovery_signature};
+use garden_sensor_core::payload::{
+    add_timestamp, batch_json, buffered_json, fill_placeholders, generate_json, valid_timestamp,
+    values_payload,
+};
+use garden_sensor_core::reading_buffer::ReadingBuffer;
+use garden_sensor_core::sensors::battery_sensor::BatterySensor;
+use garden_sensor_core::sensors::sensor::{endpoints, SensorsVec};
+use garden_sensor_core::template::{generate_html_value, AccessPoint};
+use garden_sensor_core::wifi_networks::backoff_sleep;
 use log::{error, info};
-use sensors::battery_sensor::BatterySensor;
-use sensors::sensor::Sensor;
-use serde_json::json;
-use serde_json::Map;
-use url_encoded_data::UrlEncodedData;
 
-#[allow(unused_imports)]
-use sensors::hcsr04_sensor::HCSR04Sensor;
-#[allow(unused_imports)]
-use sensors::moisture_sensor::MoistureSensor;
+use http_helper::MAX_SETTINGS_BODY_LEN;
+use sensors::esp_hal;
+use sensors::sensor_profile;
+use string_error::{StringError, StringEspError};
 
 mod sensors {
-    pub mod aht10_sensor;
-    pub mod battery_sensor;
-    pub mod hcsr04_sensor;
-    pub mod moisture_sensor;
-    pub mod sensor;
+    pub mod esp_hal;
+    pub mod sensor_profile;
 }
 
 mod configuration {
@@ -53,14 +72,82 @@ mod configuration {
     pub mod nvs_configuration;
 }
 
+mod dns_helper;
+mod http_helper;
+mod mdns_helper;
+mod mqtt_helper;
+mod ota_helper;
+mod sntp_helper;
 mod string_error;
 mod template;
 mod wifi_helper;
 
-type SensorsVec = Vec<Box<dyn Sensor + Send>>;
+/// Connectivity checks of Android, iOS/macOS and Windows, redirected to the
+/// settings page so the system opens it as a captive portal
+const CAPTIVE_PORTAL_PROBES: &[&str] = &[
+    "/generate_204",
+    "/gen_204",
+    "/hotspot-detect.html",
+    "/library/test/success.html",
+    "/connecttest.txt",
+    "/ncsi.txt",
+    "/redirect",
+];
+
+const JSON_HEADERS: [(&str, &str); 1] = [("Content-Type", "application/json")];
 
 static mut ADC_1: Option<AdcDriver<ADC1>> = None;
 
+const READING_BUFFER_SLOTS: usize = 16;
+
+/// `ESP_ERR_ESP_TLS_*` and `ESP_ERR_MBEDTLS_*` codes (handshake, certificate
+/// check), see `esp_tls_errors.h`
+const ESP_TLS_ERRORS: RangeInclusive<i32> = 0x8000..=0x80ff;
+
+// State kept across wake-ups lives in RTC memory, which is powered during
+// deep sleep: `.rtc.data` is initialized on power-on, `.rtc_noinit` is never
+// initialized so it also survives resets (e.g. the reboot after an update).
+
+/// Readings not uploaded yet
+#[link_section = ".rtc_noinit"]
+static mut READING_BUFFER: ReadingBuffer<READING_BUFFER_SLOTS> = ReadingBuffer::new();
+
+fn reading_buffer() -> &'static mut ReadingBuffer<READING_BUFFER_SLOTS> {
+    unsafe { &mut *addr_of_mut!(READING_BUFFER) }
+}
+
+/// Wake-ups since the last upload in batch mode
+#[link_section = ".rtc.data"]
+static mut WAKES_SINCE_UPLOAD: u8 = 0;
+
+fn wakes_since_upload() -> &'static mut u8 {
+    unsafe { &mut *addr_of_mut!(WAKES_SINCE_UPLOAD) }
+}
+
+/// Wi-Fi connections failed in a row
+#[link_section = ".rtc.data"]
+static mut WIFI_FAILURES: u8 = 0;
+
+fn wifi_failures() -> &'static mut u8 {
+    unsafe { &mut *addr_of_mut!(WIFI_FAILURES) }
+}
+
+/// Values of the last reported readings
+#[link_section = ".rtc.data"]
+static mut LAST_REPORT: LastReport<16> = LastReport::new();
+
+fn last_report() -> &'static mut LastReport<16> {
+    unsafe { &mut *addr_of_mut!(LAST_REPORT) }
+}
+
+/// Signature of the last published Home Assistant discovery messages
+#[link_section = ".rtc.data"]
+static mut PUBLISHED_DISCOVERY: u32 = 0;
+
+fn published_discovery() -> &'static mut u32 {
+    unsafe { &mut *addr_of_mut!(PUBLISHED_DISCOVERY) }
+}
+
 fn adc1_ref() -> &'static AdcDriver<'static, ADC1> {
     unsafe { ADC_1.as_ref().unwrap() }
 }
@@ -69,10 +156,24 @@ fn main() -> anyhow::Result<()> {
     esp_idf_svc::hal::sys::link_patches();
     esp_idf_svc::log::EspLogger::initialize_default();
 
+    reading_buffer().validate();
+
     let peripherals = Peripherals::take()?;
-    let main_config = NvsConfiguration::new()?;
+    let mut main_config = NvsConfiguration::new(EspConfigStore::take()?);
     let pins = peripherals.pins;
 
+    match main_config.migrate_sensor_profile() {
+        Result::Ok(true) => info!("Sensor profile of the previous firmware stored"),
+        Result::Ok(false) => {}
+        Err(e) => error!("Failed to store the previous sensor profile: {}", e),
+    }
+
+    match main_config.init_ap_password(&random_bytes()) {
+        Result::Ok(Some(password)) => info!("Settings password generated: {}", password),
+        Result::Ok(None) => {}
+        Err(e) => error!("Failed to store the settings password: {}", e),
+    }
+
     let mut led_orange = PinDriver::output(pins.gpio0)?;
     let mut led_green = PinDriver::output(pins.gpio1)?;
     let config_button = PinDriver::input(pins.gpio7)?;
@@ -103,54 +204,149 @@ fn main() -> anyhow::Result<()> {
 
     let mut sensors: SensorsVec = Vec::new();
 
-    sensors.push(Box::new(BatterySensor::new(pins.gpio3, adc1_ref())?));
+    sensors.push(Box::new(BatterySensor::new(esp_hal::adc_channel(
+        adc1_ref(),
+        pins.gpio3,
+    )?)));
 
-    #[cfg(feature = "moisture-sensor")]
-    sensors.push(Box::new(MoistureSensor::new(
+    sensors.extend(sensor_profile::build_sensors(
+        &main_config,
+        #[cfg(feature = "moisture-sensor")]
         adc1_ref(),
-        pins.gpio4,
-        pins.gpio6,
-        main_config.get_vlow_moisture(),
-        main_config.get_vhigh_moisture(),
-    )?));
-
-    #[cfg(feature = "water-level-sensor")]
-    sensors.push(Box::new(HCSR04Sensor::new(
-        pins.gpio6,
-        pins.gpio4,
-        pins.gpio5,
-        main_config.get_low_water_level(),
-        main_config.get_high_water_level(),
-    )?));
+        #[cfg(feature = "aht10-sensor")]
+        peripherals.i2c0,
+    ));
 
     FreeRtos::delay_ms(3000);
 
     if config_button.is_high() {
-        let wifi = wifi_helper::connect_wifi(&main_config, peripherals.modem);
+        led_green.set_high()?;
 
-        if wifi.is_ok() {
-            error!(
-                "[MAIN SENSOR] {}",
-                main_sensor(main_config, &mut led_green, sensors).unwrap_err()
+        FreeRtos::delay_ms(500);
+
+        let timestamp = sntp_helper::now_secs();
+        let mut readings = collect_readings(&main_config, &mut sensors, timestamp);
+        let backlog = reading_buffer();
+        let batch_size = main_config.get_batch_size();
+
+        // A just updated firmware must upload once to confirm it works
+        let pending_verify = ota_helper::is_pending_verify();
+
+        let last_report = last_report();
+        if !pending_verify
+            && !last_report.should_report(&readings, &main_config.get_report_thresholds())
+        {
+            info!(
+                "No significant change for {} wake-up(s), going to sleep !",
+                last_report.wakes_since_report()
             );
-        } else {
-            error!("[WIFI] {}", wifi.err().unwrap());
+            led_green.set_low()?;
+
+            unsafe {
+                esp_deep_sleep(main_config.get_deep_sleep_duration());
+            }
         }
+        let reported = readings.clone();
 
-        error!("Retry connection in 5 seconds...");
-        let start = SystemTime::now();
-        loop {
-            led_green.set_high()?;
-            FreeRtos::delay_ms(100);
-            led_green.set_low()?;
-            FreeRtos::delay_ms(100);
+        if batch_size > 1 {
+            buffer_readings(backlog, timestamp, &readings);
+            readings.clear();
+
+            let wakes = wakes_since_upload();
+            *wakes = wakes.saturating_add(1);
+
+            // Upload early when the next wake-up would overwrite readings
+            let room_left = backlog.capacity() - backlog.len();
+            if !pending_verify && *wakes < batch_size && room_left >= endpoints(&sensors).len() {
+                info!(
+                    "Batch {}/{}, {} reading(s) buffered, going to sleep !",
+                    wakes,
+                    batch_size,
+                    backlog.len()
+                );
+                led_green.set_low()?;
+
+                unsafe {
+                    esp_deep_sleep(main_config.get_deep_sleep_duration());
+                }
+            }
+
+            *wakes = 0;
+        }
+
+        let unsent = match wifi_helper::connect_wifi(&main_config, peripherals.modem) {
+            Result::Ok(_wifi) => {
+                *wifi_failures() = 0;
+                let _mdns = start_mdns(&main_config, false);
+
+                match main_sensor(&mut main_config, &sensors, &readings, backlog) {
+                    Result::Ok(unsent) => unsent,
+                    Err(e) => {
+                        error!("[MAIN SENSOR] {}", e);
+                        readings
+                    }
+                }
+            }
+            Err(e) => {
+                error!("[WIFI] {}", e);
+                *wifi_failures() = wifi_failures().saturating_add(1);
+                readings
+            }
+        };
+
+        // In batch mode the readings of this wake-up are delivered with the
+        // buffered ones
+        let delivered = unsent.is_empty() && (batch_size <= 1 || backlog.is_empty());
+
+        if delivered {
+            // A new firmware is only kept once it delivered its readings
+            if let Err(e) = ota_helper::mark_valid() {
+                error!("[OTA] {}", e);
+            }
+
+            last_report.record(&reported);
+        }
+
+        if !unsent.is_empty() {
+            buffer_readings(backlog, timestamp, &unsent);
+
+            error!(
+                "{} reading(s) buffered until the next wake-up",
+                backlog.len()
+            );
 
-            if start.elapsed().unwrap().as_secs() >= 5 {
-                esp_idf_svc::hal::reset::restart();
+            let start = SystemTime::now();
+            while start.elapsed().unwrap().as_secs() < 5 {
+                led_green.set_high()?;
+                FreeRtos::delay_ms(100);
+                led_green.set_low()?;
+                FreeRtos::delay_ms(100);
             }
         }
+
+        // Sleep longer while no network can be joined, to save the battery
+        let sleep = backoff_sleep(main_config.get_deep_sleep_duration(), *wifi_failures());
+
+        // Time since boot, to compare DHCP and static IP wake-ups
+        let awake_ms = unsafe { esp_timer_get_time() } / 1000;
+
+        info!(
+            "Awake for {} ms, going to sleep for {} s !",
+            awake_ms,
+            sleep / 1_000_000
+        );
+        led_green.set_low()?;
+
+        unsafe {
+            esp_deep_sleep(sleep);
+        }
     } else {
-        let wifi = wifi_helper::create_ap(peripherals.modem);
+        // Recovery of a forgotten settings password: it is printed on the
+        // serial console each time settings mode starts, which takes physical
+        // access to the button and USB port anyway
+        info!("Settings password: {}", main_config.get_ap_password());
+
+        let wifi = wifi_helper::create_ap(&main_config, peripherals.modem);
 
         if wifi.is_ok() {
             error!(
@@ -166,30 +362,6 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn generate_json(sensors: &mut SensorsVec, main_config: &NvsConfiguration) -> serde_json::Value {
-    let mut map = Map::new();
-
-    map.insert("id".to_string(), json!(main_config.get_id()));
-    map.insert("name".to_string(), json!(main_config.get_name()));
-
-    for sensor in sensors {
-        sensor.add_json_value(&mut map);
-    }
-
-    serde_json::Value::Object(map)
-}
-
-fn generate_html_value(sensors: &mut SensorsVec) -> String {
-    let mut result = String::new();
-
-    for sensor in sensors {
-        result += &sensor.pretty_print();
-        result += "\n";
-    }
-
-    result
-}
-
 fn main_settings<LedO: Pin>(
     main_config: NvsConfiguration,
     wifi: BlockingWifi<EspWifi>,
@@ -198,135 +370,543 @@ fn main_settings<LedO: Pin>(
 ) -> anyhow::Result<()> {
     led_orange.set_high()?;
 
+    let _mdns = start_mdns(&main_config, true);
+
     let mutex_config = Mutex::new(main_config);
     let mutex_wifi = Mutex::new(wifi);
     let mutex_sensor = Mutex::new(sensors);
+    let restart = AtomicBool::new(false);
+    let portal_url = format!("http://{}/", wifi_helper::AP_ADDRESS);
 
     let mut server = EspHttpServer::new(&http::server::Configuration {
         stack_size: 10240,
         ..Default::default()
     })?;
 
-    server.fn_handler("/", Method::Get, |req| {
-        req.into_ok_response()?
-            .write_all(
-                template::to_html(
-                    &mutex_config.lock().unwrap(),
-                    None,
-                    mutex_wifi.lock().unwrap().scan().ok(),
-                    &generate_html_value(&mut mutex_sensor.lock().unwrap()), // &mutex_board.lock().unwrap().sensors.sensor_string_value(),
-                )
-                .as_bytes(),
+    server.fn_handler::<anyhow::Error, _>("/", Method::Get, |req| {
+        if !is_authorized(&req, &mutex_config) {
+            return unauthorized(req);
+        }
+
+        req.into_ok_response()?.write_all(
+            template::to_html(
+                &mutex_config.lock().unwrap(),
+                None,
+                mutex_wifi.lock().unwrap().scan().ok(),
+                &generate_html_value(&mut mutex_sensor.lock().unwrap()), // &mutex_board.lock().unwrap().sensors.sensor_string_value(),
             )
-            .map(|_| ())
+            .as_bytes(),
+        )?;
+        Ok(())
     })?;
 
     server.fn_handler::<anyhow::Error, _>("/", Method::Post, |mut req| {
-        let len_body = req
-            .header("Content-Length")
-            .and_then(|v| v.parse::<u64>().ok())
-            .unwrap_or(0);
+        if !is_authorized(&req, &mutex_config) {
+            return unauthorized(req);
+        }
 
-        let error_message: String;
+        let error_message = match http_helper::read_body(&mut req, MAX_SETTINGS_BODY_LEN) {
+            Result::Ok(body) => {
+                main_configuration::apply_form_body(&mut mutex_config.lock().unwrap(), &body)
+            }
+            Err(e) => format!("Save error: {}", e),
+        };
 
-        if len_body == 0 {
-            error_message = "Save error: No body or no content-length".to_string();
-        } else if len_body >= 256 {
-            error_message = "Save error: Content-length too long.".to_string();
-        } else {
-            let mut buffer = [0u8; 256];
+        req.into_ok_response()?.write_all(
+            template::to_html(
+                &mutex_config.lock().unwrap(),
+                Some(error_message),
+                mutex_wifi.lock().unwrap().scan().ok(),
+                &generate_html_value(&mut mutex_sensor.lock().unwrap()), // &mutex_board.lock().unwrap().sensors.sensor_string_value(),
+            )
+            .as_bytes(),
+        )?;
+        Ok(())
+    })?;
+
+    server.fn_handler::<anyhow::Error, _>("/ca_cert", Method::Post, |mut req| {
+        if !is_authorized(&req, &mutex_config) {
+            return unauthorized(req);
+        }
 
-            match req.read(&mut buffer) {
-                Result::Ok(bytes_read) => {
-                    let post_str = String::from_utf8(buffer[0..bytes_read].to_vec())?;
-                    let post_data = UrlEncodedData::parse_str(&post_str);
+        let message = match http_helper::read_body(&mut req, MAX_CA_CERT_LEN) {
+            Result::Ok(body) => match from_utf8(&body) {
+                Result::Ok(pem) => match mutex_config.lock().unwrap().store_ca_certificate(pem) {
+                    Result::Ok(_) if pem.trim().is_empty() => "CA certificate removed".to_string(),
+                    Result::Ok(_) => "CA certificate saved".to_string(),
+                    Err(e) => format!("CA certificate error: {}", e),
+                },
+                Err(_) => "CA certificate error: not a text file".to_string(),
+            },
+            Err(e) => format!("CA certificate error: {}", e),
+        };
 
-                    let mut mainconfig_lock = mutex_config.lock().unwrap();
+        req.into_ok_response()?.write_all(message.as_bytes())?;
+        Ok(())
+    })?;
 
-                    for elem in main_configuration::MAP_NVS_FORM {
-                        if post_data.exists(elem.form_name) {
-                            let data = post_data.get_first(&elem.form_name).unwrap();
+    for probe in CAPTIVE_PORTAL_PROBES {
+        server.fn_handler::<anyhow::Error, _>(probe, Method::Get, |req| {
+            req.into_response(302, Some("Found"), &[("Location", portal_url.as_str())])?;
+            Ok(())
+        })?;
+    }
 
-                            match elem.data_type {
-                                main_configuration::MapFormType::String(_, max_size) => {
-                                    mainconfig_lock.store_string(&elem.nvs_key, data, max_size)?
-                                }
+    if let Err(e) = dns_helper::start(wifi_helper::AP_ADDRESS) {
+        error!("[DNS] Failed to start captive portal DNS: {}", e);
+    }
 
-                                main_configuration::MapFormType::Float(_) => mainconfig_lock
-                                    .store_float(&elem.nvs_key, f32::from_str(data).unwrap())?,
+    server.fn_handler::<anyhow::Error, _>("/api/config", Method::Get, |req| {
+        if !is_authorized(&req, &mutex_config) {
+            return unauthorized(req);
+        }
 
-                                main_configuration::MapFormType::U32Hex(_) => mainconfig_lock
-                                    .store_u32(
-                                        &elem.nvs_key,
-                                        u32::from_str_radix(data, 16).unwrap(),
-                                    )?,
+        let body = api::config_json(&mutex_config.lock().unwrap()).to_string();
 
-                                main_configuration::MapFormType::Unsigned64(_) => {
-                                    mainconfig_lock
-                                        .store_u64(&elem.nvs_key, u64::from_str(data).unwrap())?
-                                }
+        req.into_response(200, None, &JSON_HEADERS)?
+            .write_all(body.as_bytes())?;
+        Ok(())
+    })?;
 
-                                main_configuration::MapFormType::Unsigned8(_) => mainconfig_lock
-                                    .store_u8(&elem.nvs_key, u8::from_str(data).unwrap())?,
-                            };
-                        }
-                    }
-                    error_message = "Save successfully!".to_string();
-                }
-                Err(_) => {
-                    error_message = "Save error: Failed to read request.".to_string();
-                }
-            };
+    server.fn_handler::<anyhow::Error, _>("/api/config", Method::Put, |mut req| {
+        if !is_authorized(&req, &mutex_config) {
+            return unauthorized(req);
         }
 
-        req.into_ok_response()?.write_all(
-            template::to_html(
-                &mutex_config.lock().unwrap(),
-                Some(error_message),
-                mutex_wifi.lock().unwrap().scan().ok(),
-                &generate_html_value(&mut mutex_sensor.lock().unwrap()), // &mutex_board.lock().unwrap().sensors.sensor_string_value(),
-            )
-            .as_bytes(),
-        )?;
+        let result = match http_helper::read_body(&mut req, MAX_SETTINGS_BODY_LEN) {
+            Result::Ok(body) => match from_utf8(&body) {
+                Result::Ok(body) => api::apply_config(&mut mutex_config.lock().unwrap(), body),
+                Err(_) => Err(StringError("Body is not UTF-8").into()),
+            },
+            Err(e) => Err(e.into()),
+        };
+
+        let (status, body) = match result {
+            Result::Ok(count) => (200, api::updated_json(count)),
+            Err(e) => (400, api::settings_error_json(&e)),
+        };
+
+        req.into_response(status, None, &JSON_HEADERS)?
+            .write_all(body.to_string().as_bytes())?;
         Ok(())
     })?;
 
-    loop {}
+    server.fn_handler::<anyhow::Error, _>("/api/sensors", Method::Get, |req| {
+        if !is_authorized(&req, &mutex_config) {
+            return unauthorized(req);
+        }
+
+        let config = mutex_config.lock().unwrap();
+        let body = api::sensors_json(
+            config.get_id(),
+            &config.get_name(),
+            &mut mutex_sensor.lock().unwrap(),
+        )
+        .to_string();
 
-    #[allow(unreachable_code)]
+        req.into_response(200, None, &JSON_HEADERS)?
+            .write_all(body.as_bytes())?;
+        Ok(())
+    })?;
+
+    server.fn_handler::<anyhow::Error, _>("/api/wifi/scan", Method::Get, |req| {
+        if !is_authorized(&req, &mutex_config) {
+            return unauthorized(req);
+        }
+
+        let aps: Vec<AccessPoint> = mutex_wifi
+            .lock()
+            .unwrap()
+            .scan()?
+            .iter()
+            .map(template::to_access_point)
+            .collect();
+        let body = api::access_points_json(&aps).to_string();
+
+        req.into_response(200, None, &JSON_HEADERS)?
+            .write_all(body.as_bytes())?;
+        Ok(())
+    })?;
+
+    server.fn_handler::<anyhow::Error, _>("/ota", Method::Post, |mut req| {
+        if !is_authorized(&req, &mutex_config) {
+            return unauthorized(req);
+        }
+
+        let message = match ota_helper::write_update(&mut req, None, None) {
+            Result::Ok(_) => {
+                restart.store(true, Ordering::Relaxed);
+                "Firmware updated, restarting...".to_string()
+            }
+            Err(e) => format!("Firmware update error: {}", e),
+        };
+
+        req.into_ok_response()?.write_all(message.as_bytes())?;
+        Ok(())
+    })?;
+
+    // The settings portal works, confirm a just updated firmware
+    if let Err(e) = ota_helper::mark_valid() {
+        error!("[OTA] {}", e);
+    }
+
+    loop {
+        if restart.load(Ordering::Relaxed) {
+            // Let the HTTP response reach the browser
+            FreeRtos::delay_ms(1000);
+            esp_idf_svc::hal::reset::restart();
+        }
+
+        FreeRtos::delay_ms(100);
+    }
+}
+
+/// Bytes of the hardware random number generator. Its entropy source is
+/// enabled while reading, as the radio is not running yet.
+fn random_bytes() -> [u8; AP_PASSWORD_LEN] {
+    let mut bytes = [0u8; AP_PASSWORD_LEN];
+
+    unsafe {
+        bootloader_random_enable();
+        bootloader_fill_random(bytes.as_mut_ptr().cast(), bytes.len());
+        bootloader_random_disable();
+    }
+
+    bytes
+}
+
+/// Start the mDNS responder, errors are only logged: the device stays
+/// reachable by IP address.
+fn start_mdns(config: &NvsConfiguration, http: bool) -> Option<EspMdns> {
+    match mdns_helper::start(&wifi_helper::hostname(config), &config.get_name(), http) {
+        Result::Ok(mdns) => Some(mdns),
+        Err(e) => {
+            error!("[MDNS] {}", e);
+            None
+        }
+    }
+}
+
+type SettingsRequest<'r, 'c> = http::server::Request<&'r mut http::server::EspHttpConnection<'c>>;
+
+/// `true` when the request carries the settings password
+fn is_authorized(req: &SettingsRequest, config: &Mutex<NvsConfiguration>) -> bool {
+    auth::is_authorized(
+        req.header("Authorization"),
+        &config.lock().unwrap().get_ap_password(),
+    )
+}
+
+/// Ask the browser for the settings login
+fn unauthorized(req: SettingsRequest) -> anyhow::Result<()> {
+    req.into_response(
+        401,
+        Some("Unauthorized"),
+        &[("WWW-Authenticate", "Basic realm=\"Garden sensor\"")],
+    )?
+    .write_all(b"Unauthorized")?;
     Ok(())
 }
 
-fn main_sensor<LedG: Pin>(
-    main_config: NvsConfiguration,
-    led_green: &mut PinDriver<'_, LedG, Output>,
-    mut sensors: SensorsVec,
-) -> anyhow::Result<()> {
-    led_green.set_high()?;
+/// Measure every sensor, one JSON payload per endpoint.
+fn collect_readings(
+    main_config: &NvsConfiguration,
+    sensors: &mut SensorsVec,
+    timestamp: u64,
+) -> Vec<(&'static str, String)> {
+    let endpoints = endpoints(sensors);
+
+    if endpoints.is_empty() {
+        log::warn!("No sensor enabled, nothing to send");
+    }
+
+    let mut payloads: Vec<(&'static str, String)> = Vec::new();
+
+    for endpoint in endpoints {
+        let payload_json = generate_json(
+            main_config.get_id(),
+            &main_config.get_name(),
+            sensors,
+            endpoint,
+            valid_timestamp(timestamp),
+        )
+        .to_string();
+
+        info!("JSON DATA ({}): {}", endpoint, payload_json);
+
+        payloads.push((endpoint, payload_json));
+    }
+
+    payloads
+}
+
+fn buffer_readings(
+    backlog: &mut ReadingBuffer<READING_BUFFER_SLOTS>,
+    timestamp: u64,
+    readings: &[(&'static str, String)],
+) {
+    for (endpoint, payload) in readings {
+        let stored = values_payload(payload)
+            .is_some_and(|values| backlog.push(timestamp, endpoint, &values));
+
+        if !stored {
+            error!("Reading for '{}' could not be buffered, dropped", endpoint);
+        }
+    }
+}
+
+/// Upload the readings of this wake-up, then the readings buffered by
+/// previous wake-ups (failed uploads or batch mode). Returns the readings of
+/// this wake-up that were not delivered.
+fn main_sensor(
+    main_config: &mut NvsConfiguration,
+    sensors: &SensorsVec,
+    readings: &[(&'static str, String)],
+    backlog: &mut ReadingBuffer<READING_BUFFER_SLOTS>,
+) -> anyhow::Result<Vec<(&'static str, String)>> {
+    if let Err(e) = sntp_helper::sync_time(main_config) {
+        log::warn!("[SNTP] {}", e);
+    }
+
+    // Readings taken before the clock was ever synchronized get the upload time
+    let payloads: Vec<(&str, String)> = match valid_timestamp(sntp_helper::now_secs()) {
+        Some(now) => readings
+            .iter()
+            .map(|(endpoint, payload)| (*endpoint, add_timestamp(payload, now)))
+            .collect(),
+        None => readings.to_vec(),
+    };
 
-    FreeRtos::delay_ms(500);
+    let mut discovery =
+        if main_config.get_uplink() == Uplink::Mqtt && main_config.is_ha_discovery_enabled() {
+            discovery_messages(
+                main_config.get_id(),
+                &main_config.get_name(),
+                sensors,
+                &main_config.get_mqtt_topic(),
+            )
+        } else {
+            Vec::new()
+        };
+
+    // Retained by the broker, so only published after a power-on or when
+    // they change
+    let signature = discovery_signature(&discovery);
+    if signature == *published_discovery() {
+        discovery.clear();
+    }
 
-    let url = main_configuration::make_http_url(&main_config);
-    let payload_json = generate_json(&mut sensors, &main_config).to_string();
+    let results = upload(main_config, &discovery, &payloads)?;
+    *published_discovery() = signature;
 
-    info!("Send data to: '{}'", url);
-    info!("JSON DATA: {}", payload_json);
+    let mut responses = Vec::new();
+    let mut unsent = Vec::new();
 
-    let mut client: HttpClient<EspHttpConnection> =
-        HttpClient::wrap(EspHttpConnection::new(&Default::default())?);
+    for (reading, result) in readings.iter().zip(results) {
+        match result {
+            Result::Ok(body) => responses.push(body),
+            Err(e) => {
+                log::warn!("Reading for '{}' not delivered: {}", reading.0, e);
+                unsent.push(reading.clone());
+            }
+        }
+    }
 
-    let headers = [
+    if !backlog.is_empty() {
+        info!("Flush {} buffered reading(s)", backlog.len());
+
+        // Each MQTT message stays a single reading so subscribers (e.g. Home
+        // Assistant templates) see the usual document
+        let id = main_config.get_id();
+        let name = main_config.get_name();
+        let buffered: Vec<(String, String)> = match main_config.get_uplink() {
+            Uplink::Mqtt => backlog
+                .iter()
+                .filter_map(|r| {
+                    Some((
+                        r.endpoint.to_string(),
+                        buffered_json(&r, id, &name)?.to_string(),
+                    ))
+                })
+                .collect(),
+            Uplink::Http => batch_json(backlog.iter(), id, &name)
+                .into_iter()
+                .map(|(endpoint, batch)| (endpoint, batch.to_string()))
+                .collect(),
+        };
+
+        match upload(main_config, &[], &buffered) {
+            Result::Ok(results) => {
+                let mut failed = Vec::new();
+
+                for ((endpoint, _), result) in buffered.iter().zip(results) {
+                    match result {
+                        Result::Ok(body) => responses.push(body),
+                        Err(e) => {
+                            log::warn!("Buffered readings for '{}' not delivered: {}", endpoint, e);
+                            failed.push(endpoint);
+                        }
+                    }
+                }
+
+                backlog.retain(|r| failed.iter().any(|e| *e == r.endpoint));
+            }
+            Err(e) => log::warn!("Failed to flush buffered readings: {}", e),
+        }
+
+        if !backlog.is_empty() {
+            log::warn!(
+                "{} buffered reading(s) kept for the next wake-up",
+                backlog.len()
+            );
+        }
+    }
+
+    for response in responses {
+        match main_configuration::apply_json(main_config, &response) {
+            Result::Ok(0) => (),
+            Result::Ok(count) => info!("{} setting(s) updated by the server", count),
+            Err(e) => log::warn!("Server config rejected: {}", e),
+        }
+    }
+
+    let ota_url = main_config.get_ota_url();
+    if !ota_url.is_empty() && main_config.get_http_tls() == HttpTls::None {
+        log::warn!("[OTA] Update check skipped, it needs HTTPS with a certificate check");
+    } else if !ota_url.is_empty() {
+        let mut client: HttpClient<EspHttpConnection> = HttpClient::wrap(EspHttpConnection::new(
+            &http_client_configuration(main_config)?,
+        )?);
+        let url = main_configuration::make_url(main_config, &ota_url, "ota");
+
+        // The new firmware boots on the next wake-up
+        if let Err(e) = ota_helper::check_update(&mut client, &url) {
+            log::warn!("[OTA] {}", e);
+        }
+    }
+
+    Ok(unsent)
+}
+
+/// Send `(endpoint, payload)` with the configured uplink. Returns for each
+/// payload its HTTP response body or why it was not delivered, an error when
+/// none could be sent.
+fn upload<E: AsRef<str>>(
+    main_config: &NvsConfiguration,
+    discovery: &[(String, String)],
+    payloads: &[(E, String)],
+) -> anyhow::Result<Vec<anyhow::Result<String>>> {
+    match main_config.get_uplink() {
+        Uplink::Mqtt => {
+            let topic = main_config.get_mqtt_topic();
+            let messages: Vec<(String, String)> = payloads
+                .iter()
+                .map(|(endpoint, payload)| {
+                    (
+                        fill_placeholders(
+                            &topic,
+                            main_config.get_id(),
+                            &main_config.get_name(),
+                            endpoint.as_ref(),
+                        ),
+                        payload.clone(),
+                    )
+                })
+                .collect();
+
+            mqtt_helper::publish(main_config, discovery, &messages)?;
+
+            Ok(payloads.iter().map(|_| Ok(String::new())).collect())
+        }
+        Uplink::Http => {
+            let mut client: HttpClient<EspHttpConnection> = HttpClient::wrap(
+                EspHttpConnection::new(&http_client_configuration(main_config)?)?,
+            );
+
+            let http_headers = main_config.get_http_headers();
+            let mut responses = Vec::new();
+
+            for (endpoint, payload_json) in payloads {
+                let url = main_configuration::make_http_url(main_config, endpoint.as_ref());
+
+                info!("Send data to: '{}'", url);
+
+                responses.push(send_payload(
+                    &mut client,
+                    main_config.get_http_method(),
+                    &url,
+                    &http_headers,
+                    payload_json,
+                ));
+            }
+
+            Ok(responses)
+        }
+    }
+}
+
+fn http_client_configuration(
+    config: &NvsConfiguration,
+) -> anyhow::Result<http::client::Configuration> {
+    let mut http_config = http::client::Configuration::default();
+
+    match config.get_http_tls() {
+        HttpTls::None => (),
+        HttpTls::Bundle => {
+            http_config.crt_bundle_attach = Some(esp_idf_svc::hal::sys::esp_crt_bundle_attach);
+        }
+        HttpTls::CustomCa => {
+            let cert = config.get_ca_certificate().ok_or(StringError(
+                "HTTPS with custom CA but no CA certificate stored",
+            ))?;
+
+            // ESP-TLS parses the PEM into its own store, `cert` can be dropped
+            esp!(unsafe { esp_tls_set_global_ca_store(cert.as_ptr(), cert.len() as u32) })
+                .map_err(|e| StringEspError("Invalid CA certificate", e))?;
+
+            http_config.use_global_ca_store = true;
+        }
+    }
+
+    Ok(http_config)
+}
+
+fn send_payload(
+    client: &mut HttpClient<EspHttpConnection>,
+    method: HttpMethod,
+    url: &str,
+    custom_headers: &[(String, String)],
+    payload_json: &str,
+) -> anyhow::Result<String> {
+    let content_length = format!("{}", payload_json.len());
+    let mut headers = vec![
         ("content-type", "application/json"),
-        ("content-length", &format!("{}", payload_json.len())),
+        ("content-length", content_length.as_str()),
     ];
+    headers.extend(
+        custom_headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str())),
+    );
+
+    let method = match method {
+        HttpMethod::Post => Method::Post,
+        HttpMethod::Put => Method::Put,
+    };
 
     for attempt in 1..=5 {
         info!("Send data to server (attempt {}/5)", attempt);
 
-        let mut request = match client.post(&url, &headers) {
+        let mut request = match client.request(method, url, &headers) {
             Result::Ok(req) => req,
             Err(e) => {
-                log::warn!("Fail to create post: {}", e);
+                log::warn!("Fail to create request: {}", e);
+                if let Some(tls_error) = last_tls_error(client) {
+                    error!(
+                        "TLS connection to '{}' failed ({}), check that the server certificate is signed by the configured CA",
+                        url, tls_error
+                    );
+                }
                 continue;
             }
         };
@@ -335,26 +915,43 @@ fn main_sensor<LedG: Pin>(
 
         match request.submit() {
             Result::Ok(mut response) => {
-                info!(
-                    "Server response:\n\tStatus: {}\n\tBody: {}",
-                    response.status(),
-                    extract_data_or(&mut response)
-                );
-                break;
+                let status = response.status();
+                let body = extract_data_or(&mut response);
+                info!("Server response:\n\tStatus: {}\n\tBody: {}", status, body);
+
+                // Only a success delivers the readings, a server error may
+                // be temporary
+                match status {
+                    200..=299 => return Ok(body),
+                    500..=599 => log::warn!("Server error {}, retrying", status),
+                    _ => {
+                        log::warn!("Server rejected the data with status {}", status);
+                        return Err(StringError("Data rejected by the server").into());
+                    }
+                }
             }
             Err(error) => log::warn!("Failed to send data to server:\n\t{}", error),
         }
     }
 
-    info!("Going to sleep !");
-    led_green.set_low()?;
+    Err(StringError("Failed to send data to server after 5 attempts").into())
+}
 
-    unsafe {
-        esp_deep_sleep(main_config.get_deep_sleep_duration());
-    }
+/// Last error of the client TLS layer, `None` if the connection did not fail
+/// there (e.g. DNS or TCP error, plain HTTP).
+fn last_tls_error(client: &mut HttpClient<EspHttpConnection>) -> Option<EspError> {
+    let mut tls_code = 0;
+    let mut tls_flags = 0;
+
+    let error = unsafe {
+        esp_http_client_get_and_clear_last_tls_error(
+            client.connection().handle(),
+            &mut tls_code,
+            &mut tls_flags,
+        )
+    };
 
-    #[allow(unreachable_code)]
-    Ok(())
+    EspError::from(error).filter(|e| ESP_TLS_ERRORS.contains(&e.code()))
 }
 
 fn extract_data_or(response: &mut Response<&mut EspHttpConnection>) -> String {
@@ -0,0 +1,22 @@
+//! mDNS responder, so the device is reached as `<hostname>.local` instead of
+//! by its IP address.
+
+use esp_idf_svc::mdns::EspMdns;
+use log::info;
+
+/// Answer mDNS queries for `hostname` while the returned responder lives.
+/// With `http`, the settings portal is advertised as an HTTP service.
+pub fn start(hostname: &str, name: &str, http: bool) -> anyhow::Result<EspMdns> {
+    let mut mdns = EspMdns::take()?;
+
+    mdns.set_hostname(hostname)?;
+    mdns.set_instance_name(if name.is_empty() { hostname } else { name })?;
+
+    if http {
+        mdns.add_service(None, "_http", "_tcp", 80, &[("path", "/")])?;
+    }
+
+    info!("[MDNS] Responding as {}.local", hostname);
+
+    Ok(mdns)
+}
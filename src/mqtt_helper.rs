@@ -0,0 +1,120 @@
+//! MQTT uplink.
+//!
+//! To try it against a local broker, run `mosquitto -v` on the LAN, select the
+//! MQTT uplink in the settings with the host address as broker, and watch the
+//! readings with `mosquitto_sub -v -t 'garden/#'`.
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use esp_idf_svc::mqtt::client::{
+    EspMqttClient, EventPayload, MessageId, MqttClientConfiguration, QoS,
+};
+use log::{info, warn};
+
+use crate::configuration::nvs_configuration::NvsConfiguration;
+use crate::string_error::StringError;
+
+const MQTT_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum MqttEvent {
+    Connected,
+    Published(MessageId),
+    Disconnected,
+    Error(String),
+}
+
+fn to_qos(qos: u8) -> QoS {
+    match qos {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        _ => QoS::ExactlyOnce,
+    }
+}
+
+/// Connect to the configured broker and publish every `(topic, payload)`,
+/// `discovery` messages are always retained so Home Assistant finds them.
+/// With QoS 1 or 2, wait for the broker acknowledgements before returning.
+pub fn publish(
+    config: &NvsConfiguration,
+    discovery: &[(String, String)],
+    messages: &[(String, String)],
+) -> anyhow::Result<()> {
+    let url = format!(
+        "mqtt://{}:{}",
+        config.get_mqtt_host(),
+        config.get_mqtt_port()
+    );
+    let client_id = format!("garden-{:x}", config.get_id());
+    let user = config.get_mqtt_user();
+    let password = config.get_mqtt_password();
+    let qos = config.get_mqtt_qos();
+    let retain = config.is_mqtt_retain();
+
+    info!("Connect to MQTT broker: '{}'", url);
+
+    let (tx, rx) = mpsc::channel();
+
+    let mut client = EspMqttClient::new_cb(
+        &url,
+        &MqttClientConfiguration {
+            client_id: Some(&client_id),
+            username: (!user.is_empty()).then_some(user.as_str()),
+            password: (!password.is_empty()).then_some(password.as_str()),
+            ..Default::default()
+        },
+        move |event| {
+            let event = match event.payload() {
+                EventPayload::Connected(_) => MqttEvent::Connected,
+                EventPayload::Published(id) => MqttEvent::Published(id),
+                EventPayload::Disconnected => MqttEvent::Disconnected,
+                EventPayload::Error(e) => MqttEvent::Error(e.to_string()),
+                _ => return,
+            };
+            let _ = tx.send(event);
+        },
+    )?;
+
+    loop {
+        match rx.recv_timeout(MQTT_TIMEOUT) {
+            Ok(MqttEvent::Connected) => break,
+            Ok(MqttEvent::Error(e)) => warn!("MQTT error: {}", e),
+            Ok(_) => (),
+            Err(_) => return Err(StringError("MQTT broker unreachable").into()),
+        }
+    }
+    info!("MQTT connected");
+
+    let mut pending = Vec::new();
+
+    let all_messages = discovery
+        .iter()
+        .map(|m| (m, true))
+        .chain(messages.iter().map(|m| (m, retain)));
+
+    for ((topic, payload), retain) in all_messages {
+        info!("Publish to '{}' (QoS {}, retain {})", topic, qos, retain);
+
+        let id = client.publish(topic, to_qos(qos), retain, payload.as_bytes())?;
+
+        if qos > 0 {
+            pending.push(id);
+        }
+    }
+
+    while !pending.is_empty() {
+        match rx.recv_timeout(MQTT_TIMEOUT) {
+            Ok(MqttEvent::Published(id)) => pending.retain(|p| *p != id),
+            Ok(MqttEvent::Error(e)) => warn!("MQTT error: {}", e),
+            Ok(MqttEvent::Disconnected) => {
+                return Err(StringError("MQTT broker disconnected before ack").into())
+            }
+            Ok(_) => (),
+            Err(_) => return Err(StringError("MQTT publish not acknowledged").into()),
+        }
+    }
+
+    info!("MQTT messages published");
+
+    Ok(())
+}
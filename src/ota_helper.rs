@@ -0,0 +1,161 @@
+//! Over-the-air firmware updates.
+//!
+//! The partition table has two app slots (`ota_0`, `ota_1`). An update is
+//! written to the slot not running and booted once in "pending verify" state:
+//! unless that firmware calls [`mark_valid`] before the next reset (crash,
+//! failed upload then deep sleep...), the bootloader rolls back to the
+//! previous slot.
+
+use embedded_svc::http::client::Client as HttpClient;
+use embedded_svc::ota::SlotState;
+use esp_idf_svc::hal::io::Read;
+use esp_idf_svc::http::client::EspHttpConnection;
+use esp_idf_svc::ota::{EspFirmwareInfoLoader, EspOta};
+use garden_sensor_core::ota_manifest::{require_https, UpdateManifest};
+use garden_sensor_core::sha256::Sha256;
+use log::{info, warn};
+
+use crate::http_helper;
+use crate::string_error::StringError;
+
+const CHUNK_SIZE: usize = 4096;
+
+/// Largest update manifest accepted
+const MAX_MANIFEST_LEN: usize = 1024;
+
+/// `true` when the running firmware was just updated and not confirmed yet.
+pub fn is_pending_verify() -> bool {
+    EspOta::new()
+        .and_then(|ota| ota.get_running_slot())
+        .map(|slot| slot.state == SlotState::Unverified)
+        .unwrap_or(false)
+}
+
+/// Confirm the running firmware, cancelling the rollback.
+pub fn mark_valid() -> anyhow::Result<()> {
+    let mut ota = EspOta::new()?;
+
+    if ota.get_running_slot()?.state == SlotState::Unverified {
+        ota.mark_running_slot_valid()?;
+        info!("[OTA] Running firmware marked valid");
+    }
+
+    Ok(())
+}
+
+/// Write the firmware image read from `reader` to the update slot and boot it
+/// on the next reset. With `skip_version`, an image of that version is not
+/// written and `Ok(false)` is returned. With `sha256`, an image with another
+/// digest is discarded.
+pub fn write_update<R: Read>(
+    mut reader: R,
+    skip_version: Option<&str>,
+    sha256: Option<&[u8; 32]>,
+) -> anyhow::Result<bool> {
+    let mut ota = EspOta::new()?;
+    let mut update = ota.initiate_update()?;
+    let mut loader = EspFirmwareInfoLoader::new();
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut total = 0;
+
+    loop {
+        let len = reader
+            .read(&mut buffer)
+            .map_err(|_| StringError("Failed to read firmware image"))?;
+
+        if len == 0 {
+            break;
+        }
+
+        if !loader.is_loaded() {
+            loader.load(&buffer[0..len])?;
+
+            if loader.is_loaded() {
+                let info = loader.get_info()?;
+                info!("[OTA] Receiving firmware version '{}'", info.version);
+
+                if skip_version == Some(info.version.as_str()) {
+                    info!("[OTA] Firmware already running, update skipped");
+                    return Ok(false);
+                }
+            }
+        }
+
+        update.write(&buffer[0..len])?;
+        hasher.update(&buffer[0..len]);
+        total += len;
+    }
+
+    if !loader.is_loaded() {
+        return Err(StringError("Firmware image too small").into());
+    }
+
+    if sha256.is_some_and(|expected| *expected != hasher.finish()) {
+        update.abort()?;
+        return Err(StringError("Firmware image SHA-256 mismatch, update discarded").into());
+    }
+
+    update.complete()?;
+    info!("[OTA] {} bytes written, update applied on next boot", total);
+
+    Ok(true)
+}
+
+/// Version of the running firmware
+pub fn running_version() -> Option<String> {
+    let slot = EspOta::new().and_then(|ota| ota.get_running_slot()).ok()?;
+
+    slot.firmware.map(|f| f.version.to_string())
+}
+
+/// Download the firmware described by the manifest at `url` if the server
+/// offers a version other than the running one. The server answers
+/// `204`/`304` when there is no update. Both the manifest and the image must
+/// be served over HTTPS.
+pub fn check_update(client: &mut HttpClient<EspHttpConnection>, url: &str) -> anyhow::Result<bool> {
+    require_https(url)?;
+
+    let version = running_version().unwrap_or_default();
+    let headers = [("x-firmware-version", version.as_str())];
+
+    info!("[OTA] Check update at '{}' (running '{}')", url, version);
+
+    let mut response = client
+        .request(embedded_svc::http::Method::Get, url, &headers)?
+        .submit()?;
+
+    let manifest = match response.status() {
+        200 => {
+            let body = http_helper::read_body(&mut response, MAX_MANIFEST_LEN)?;
+            UpdateManifest::parse(&String::from_utf8_lossy(&body))?
+        }
+        204 | 304 | 404 => return Ok(false),
+        status => {
+            warn!("[OTA] Unexpected update server status {}", status);
+            return Ok(false);
+        }
+    };
+    drop(response);
+
+    if manifest.version == version {
+        return Ok(false);
+    }
+
+    info!(
+        "[OTA] Download version '{}' from '{}'",
+        manifest.version, manifest.url
+    );
+
+    let response = client
+        .request(embedded_svc::http::Method::Get, &manifest.url, &headers)?
+        .submit()?;
+
+    match response.status() {
+        200 => write_update(response, Some(&version), Some(&manifest.sha256)),
+        status => {
+            warn!("[OTA] Unexpected firmware download status {}", status);
+            Ok(false)
+        }
+    }
+}
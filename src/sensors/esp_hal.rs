@@ -0,0 +1,56 @@
+use std::borrow::Borrow;
+
+use esp_idf_svc::hal::{
+    adc::{
+        attenuation::DB_11,
+        oneshot::{config::AdcChannelConfig, AdcChannelDriver, AdcDriver},
+        Adc,
+    },
+    gpio::ADCPin,
+};
+use garden_sensor_core::sensors::hal::AdcChannel;
+
+#[cfg(feature = "water-level-sensor")]
+use esp_idf_svc::hal::sys::esp_timer_get_time;
+#[cfg(feature = "water-level-sensor")]
+use garden_sensor_core::sensors::hal::Clock;
+
+/// ESP-IDF oneshot ADC channel.
+pub struct EspAdcChannel<'a, APin: ADCPin, M: Borrow<AdcDriver<'a, APin::Adc>>>(
+    AdcChannelDriver<'a, APin, M>,
+);
+
+impl<'a, ADC: Adc + 'a, APin: ADCPin<Adc = ADC>, M: Borrow<AdcDriver<'a, ADC>>> AdcChannel
+    for EspAdcChannel<'a, APin, M>
+{
+    fn read_mv(&mut self) -> Option<u16> {
+        self.0.read().ok()
+    }
+}
+
+/// Create a calibrated ADC channel with the full 0-3.3V input range.
+pub fn adc_channel<'a, ADC: Adc + 'a, APin: ADCPin<Adc = ADC>, M: Borrow<AdcDriver<'a, ADC>>>(
+    adc_driver: M,
+    pin: APin,
+) -> anyhow::Result<EspAdcChannel<'a, APin, M>> {
+    Ok(EspAdcChannel(AdcChannelDriver::new(
+        adc_driver,
+        pin,
+        &AdcChannelConfig {
+            attenuation: DB_11,
+            calibration: true,
+            ..Default::default()
+        },
+    )?))
+}
+
+/// Clock backed by the ESP high resolution timer.
+#[cfg(feature = "water-level-sensor")]
+pub struct EspClock;
+
+#[cfg(feature = "water-level-sensor")]
+impl Clock for EspClock {
+    fn now_us(&self) -> u64 {
+        unsafe { esp_timer_get_time() as u64 }
+    }
+}
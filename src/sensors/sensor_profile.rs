@@ -0,0 +1,187 @@
+use garden_sensor_core::sensors::sensor::{Sensor, SensorsVec};
+use log::{error, info};
+
+#[cfg(feature = "moisture-sensor")]
+use esp_idf_svc::hal::adc::{oneshot::AdcDriver, ADC1};
+#[cfg(any(feature = "moisture-sensor", feature = "aht10-sensor"))]
+use esp_idf_svc::hal::delay::FreeRtos;
+#[cfg(any(feature = "moisture-sensor", feature = "water-level-sensor"))]
+use esp_idf_svc::hal::gpio::{AnyOutputPin, PinDriver};
+#[cfg(feature = "moisture-sensor")]
+use esp_idf_svc::hal::gpio::{Gpio2, Gpio4};
+#[cfg(feature = "water-level-sensor")]
+use esp_idf_svc::hal::{delay::Delay, gpio::AnyInputPin};
+#[cfg(feature = "aht10-sensor")]
+use esp_idf_svc::hal::{
+    gpio::AnyIOPin,
+    i2c::{I2cConfig, I2cDriver, I2C0},
+    units::Hertz,
+};
+
+#[cfg(feature = "aht10-sensor")]
+use garden_sensor_core::sensors::aht10_sensor::{AHT10Sensor, AhtVariant};
+#[cfg(feature = "water-level-sensor")]
+use garden_sensor_core::sensors::hcsr04_sensor::HCSR04Sensor;
+#[cfg(feature = "moisture-sensor")]
+use garden_sensor_core::sensors::moisture_sensor::MoistureSensor;
+
+#[cfg(feature = "moisture-sensor")]
+use super::esp_hal;
+#[cfg(feature = "water-level-sensor")]
+use super::esp_hal::EspClock;
+use crate::{configuration::nvs_configuration::NvsConfiguration, string_error::StringError};
+
+/// GPIOs not used by the board itself (LEDs, battery, button, flash, USB, UART)
+pub const FREE_GPIOS: &[u8] = &[2, 4, 5, 6, 8, 9, 10];
+
+/// Build the sensors enabled in the stored profile. A sensor with an invalid
+/// pin assignment or failing to initialize is skipped, so the settings portal
+/// stays reachable to fix it.
+pub fn build_sensors(
+    config: &NvsConfiguration,
+    #[cfg(feature = "moisture-sensor")] adc: &'static AdcDriver<'static, ADC1>,
+    #[cfg(feature = "aht10-sensor")] i2c: I2C0,
+) -> SensorsVec {
+    let mut sensors: SensorsVec = Vec::new();
+    let mut used_pins: Vec<u8> = Vec::new();
+
+    #[cfg(feature = "moisture-sensor")]
+    if config.is_moisture_enabled() {
+        let pins = [
+            config.get_moisture_pin_adc(),
+            config.get_moisture_pin_enable(),
+        ];
+
+        match reserve_pins(&mut used_pins, &pins).and_then(|_| build_moisture(config, adc)) {
+            Ok(sensor) => sensors.push(sensor),
+            Err(e) => error!("[SENSOR] Moisture sensor (pins {:?}) disabled: {}", pins, e),
+        }
+    }
+
+    #[cfg(feature = "water-level-sensor")]
+    if config.is_water_level_enabled() {
+        let pins = [
+            config.get_water_level_pin_enable(),
+            config.get_water_level_pin_trigger(),
+            config.get_water_level_pin_echo(),
+        ];
+
+        match reserve_pins(&mut used_pins, &pins).and_then(|_| build_water_level(config)) {
+            Ok(sensor) => sensors.push(sensor),
+            Err(e) => error!(
+                "[SENSOR] Water level sensor (pins {:?}) disabled: {}",
+                pins, e
+            ),
+        }
+    }
+
+    #[cfg(feature = "aht10-sensor")]
+    if config.is_aht_enabled() {
+        let pins = [config.get_aht_pin_sda(), config.get_aht_pin_scl()];
+
+        match reserve_pins(&mut used_pins, &pins).and_then(|_| build_aht10(config, i2c)) {
+            Ok(sensor) => sensors.push(sensor),
+            Err(e) => error!("[SENSOR] AHT10 sensor (pins {:?}) disabled: {}", pins, e),
+        }
+    }
+
+    info!("[SENSOR] {} sensor(s) enabled", sensors.len());
+
+    sensors
+}
+
+fn reserve_pins(used_pins: &mut Vec<u8>, pins: &[u8]) -> anyhow::Result<()> {
+    for (i, pin) in pins.iter().enumerate() {
+        if !FREE_GPIOS.contains(pin) {
+            return Err(StringError("GPIO not available on this board").into());
+        }
+
+        if used_pins.contains(pin) || pins[..i].contains(pin) {
+            return Err(StringError("GPIO already used by another sensor").into());
+        }
+    }
+
+    used_pins.extend_from_slice(pins);
+
+    Ok(())
+}
+
+#[cfg(feature = "moisture-sensor")]
+fn build_moisture(
+    config: &NvsConfiguration,
+    adc: &'static AdcDriver<'static, ADC1>,
+) -> anyhow::Result<Box<dyn Sensor + Send>> {
+    // SAFETY: the pin has been checked against the board reserved pins and
+    // the other sensors by `reserve_pins`, so it is not driven elsewhere.
+    let pin_enable =
+        PinDriver::output(unsafe { AnyOutputPin::new(config.get_moisture_pin_enable() as i32) })?;
+
+    // Only GPIO types wired to ADC1 implement `ADCPin`, so pick the concrete one
+    let sensor: Box<dyn Sensor + Send> = match config.get_moisture_pin_adc() {
+        2 => Box::new(MoistureSensor::new(
+            esp_hal::adc_channel(adc, unsafe { Gpio2::new() })?,
+            pin_enable,
+            FreeRtos,
+            config.get_vlow_moisture(),
+            config.get_vhigh_moisture(),
+        )?),
+        4 => Box::new(MoistureSensor::new(
+            esp_hal::adc_channel(adc, unsafe { Gpio4::new() })?,
+            pin_enable,
+            FreeRtos,
+            config.get_vlow_moisture(),
+            config.get_vhigh_moisture(),
+        )?),
+        _ => return Err(StringError("Moisture ADC pin must be wired to ADC1").into()),
+    };
+
+    Ok(sensor)
+}
+
+#[cfg(feature = "water-level-sensor")]
+fn build_water_level(config: &NvsConfiguration) -> anyhow::Result<Box<dyn Sensor + Send>> {
+    // SAFETY: see `build_moisture`
+    let (pin_enable, pin_trigger, pin_echo) = unsafe {
+        (
+            AnyOutputPin::new(config.get_water_level_pin_enable() as i32),
+            AnyOutputPin::new(config.get_water_level_pin_trigger() as i32),
+            AnyInputPin::new(config.get_water_level_pin_echo() as i32),
+        )
+    };
+
+    Ok(Box::new(HCSR04Sensor::new(
+        PinDriver::output(pin_enable)?,
+        PinDriver::output(pin_trigger)?,
+        PinDriver::input(pin_echo)?,
+        Delay::new_default(),
+        EspClock,
+        config.get_low_water_level(),
+        config.get_high_water_level(),
+    )?))
+}
+
+#[cfg(feature = "aht10-sensor")]
+fn build_aht10(config: &NvsConfiguration, i2c: I2C0) -> anyhow::Result<Box<dyn Sensor + Send>> {
+    // SAFETY: see `build_moisture`
+    let (pin_sda, pin_scl) = unsafe {
+        (
+            AnyIOPin::new(config.get_aht_pin_sda() as i32),
+            AnyIOPin::new(config.get_aht_pin_scl() as i32),
+        )
+    };
+
+    let i2c_driver = I2cDriver::new(
+        i2c,
+        pin_sda,
+        pin_scl,
+        &I2cConfig::new().baudrate(Hertz(100_000)),
+    )?;
+
+    Ok(Box::new(AHT10Sensor::new(
+        i2c_driver,
+        FreeRtos,
+        AhtVariant::from(config.get_aht_variant()),
+        config.get_temperature_offset(),
+        config.get_humidity_offset(),
+    )?))
+}
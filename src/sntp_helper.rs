@@ -0,0 +1,65 @@
+//! Time synchronization. The RTC keeps the system time running across deep
+//! sleep, so SNTP is only needed after power-on and every few hours to
+//! correct the RTC drift.
+
+use std::ptr::addr_of_mut;
+use std::time::{Duration, Instant, SystemTime};
+
+use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::sntp::{EspSntp, SntpConf, SyncStatus};
+use garden_sensor_core::payload::valid_timestamp;
+use log::info;
+
+use crate::configuration::nvs_configuration::NvsConfiguration;
+use crate::string_error::StringError;
+
+const SNTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time of the last synchronization, reset on power-on
+#[link_section = ".rtc.data"]
+static mut LAST_SYNC: u64 = 0;
+
+/// Seconds since the Unix epoch, as known by the system clock
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Synchronize the system clock with the configured NTP server, unless it was
+/// done less than the configured resync interval ago.
+pub fn sync_time(config: &NvsConfiguration) -> anyhow::Result<()> {
+    let last_sync = unsafe { &mut *addr_of_mut!(LAST_SYNC) };
+    let resync_secs = config.get_ntp_resync_hours() as u64 * 3600;
+    let now = now_secs();
+
+    if *last_sync != 0
+        && valid_timestamp(now).is_some()
+        && now.saturating_sub(*last_sync) < resync_secs
+    {
+        return Ok(());
+    }
+
+    let server = config.get_ntp_server();
+    let mut conf = SntpConf::default();
+    conf.servers[0] = server.as_str();
+
+    info!("Synchronize time with '{}'", server);
+
+    let sntp = EspSntp::new(&conf)?;
+    let start = Instant::now();
+
+    while sntp.get_sync_status() != SyncStatus::Completed {
+        if start.elapsed() > SNTP_TIMEOUT {
+            return Err(StringError("SNTP synchronization timeout").into());
+        }
+
+        FreeRtos::delay_ms(100);
+    }
+
+    *last_sync = now_secs();
+    info!("Time synchronized: {}", *last_sync);
+
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use esp_idf_svc::hal::sys::esp;
+use esp_idf_svc::hal::sys::{
+    esp, esp_efuse_mac_get_default, esp_wifi_sta_get_ap_info, wifi_ap_record_t,
+};
 use esp_idf_svc::hal::sys::{esp_wifi_set_country, esp_wifi_set_max_tx_power};
 use esp_idf_svc::hal::{modem::Modem, peripheral::Peripheral, sys::wifi_country_t};
 use esp_idf_svc::wifi::AccessPointConfiguration;
@@ -9,27 +11,97 @@ use esp_idf_svc::{
     nvs::EspDefaultNvsPartition,
     wifi::{AuthMethod, BlockingWifi, ClientConfiguration, Configuration, EspWifi, WifiDriver},
 };
+use garden_sensor_core::wifi_networks::{
+    connection_order, CachedAp, Candidate, ScannedAp, WifiAuth,
+};
 use log::info;
-use std::{net::Ipv4Addr, str::FromStr};
+use std::net::Ipv4Addr;
+use std::ptr::addr_of_mut;
+use std::time::Instant;
+
+use crate::configuration::nvs_configuration::{NvsConfiguration, StaticIp};
+use crate::string_error::StringError;
+
+/// Address of the device on its settings access point
+pub const AP_ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 168, 70, 1);
+
+/// Largest SSID, in bytes
+const MAX_SSID_LEN: usize = 32;
+
+/// End of the factory MAC address, as found in the access point BSSID
+fn mac_suffix() -> String {
+    let mut mac = [0u8; 6];
+    unsafe {
+        esp_efuse_mac_get_default(mac.as_mut_ptr());
+    }
+    format!("{:02x}{:02x}{:02x}", mac[3], mac[4], mac[5])
+}
+
+/// Short identifier telling devices apart: the configured ID, or the end of
+/// the factory MAC address while no ID is set.
+pub fn device_tag(config: &NvsConfiguration) -> String {
+    match config.get_id() {
+        0 => mac_suffix(),
+        id => format!("{:x}", id),
+    }
+}
+
+/// mDNS host name, the device answers as `garden-<tag>.local`
+pub fn hostname(config: &NvsConfiguration) -> String {
+    format!("garden-{}", device_tag(config))
+}
+
+/// SSID of the settings access point: the MAC address end then the device
+/// name, cut to the SSID length. The ID is not broadcast.
+fn ap_ssid(config: &NvsConfiguration) -> String {
+    let mut ssid = format!("Garden {}", mac_suffix());
+    let name = config.get_name();
+
+    if !name.is_empty() {
+        ssid.push(' ');
+        ssid.push_str(&name);
+    }
+
+    while ssid.len() > MAX_SSID_LEN {
+        ssid.pop();
+    }
+
+    ssid
+}
+
+/// Access point of the last successful connection
+#[link_section = ".rtc.data"]
+static mut LAST_AP: Option<CachedAp> = None;
+
+fn last_ap() -> &'static mut Option<CachedAp> {
+    unsafe { &mut *addr_of_mut!(LAST_AP) }
+}
 
-use crate::configuration::nvs_configuration::NvsConfiguration;
+/// Attempts per saved network before falling back to the next one
+const ATTEMPTS_PER_NETWORK: u8 = 2;
 
+/// Join one of the saved networks: the access point of the last connection
+/// first, without scanning, then every saved network by signal strength.
 pub fn connect_wifi<'a>(
     config: &NvsConfiguration,
     modem: impl Peripheral<P = Modem> + 'a,
 ) -> anyhow::Result<BlockingWifi<EspWifi<'a>>> {
-    let wifi_configuration: Configuration = Configuration::Client(ClientConfiguration {
-        ssid: config.get_ssid().as_str().try_into().unwrap(),
-        bssid: None,
-        auth_method: AuthMethod::WPA2Personal,
-        password: config.get_passphrase().as_str().try_into().unwrap(),
-        channel: None,
-    });
+    let networks = config.get_networks();
+
+    if networks.is_empty() {
+        return Err(StringError("No Wi-Fi network saved").into());
+    }
 
     let sys_loop = EspSystemEventLoop::take()?;
     let nvs = EspDefaultNvsPartition::take()?;
 
-    let mut wifi = BlockingWifi::wrap(EspWifi::new(modem, sys_loop.clone(), Some(nvs))?, sys_loop)?;
+    let static_ip = config.get_static_ip();
+    let wifi_esp = EspWifi::new(modem, sys_loop.clone(), Some(nvs))?;
+    let mut wifi = BlockingWifi::wrap(wifi_esp, sys_loop)?;
+    let mut station = Station {
+        static_ip,
+        static_active: false,
+    };
 
     // let cc = wifi_country_t {
     //     cc: [b'F' as i8, b'R' as i8, 0 as i8],
@@ -41,55 +113,198 @@ pub fn connect_wifi<'a>(
 
     // esp!(unsafe { esp_wifi_set_country(&cc) })?;
 
-    wifi.set_configuration(&wifi_configuration)?;
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration::default()))?;
+    wifi.start()?;
+    info!("Wifi started");
 
-    for i in 1..=5 {
-        log::info!("Wifi connection attempt #{i}");
-        let _ = wifi.disconnect();
+    if let Some(candidate) = last_ap().and_then(|cached| cached.candidate(&networks)) {
+        match try_connect(&mut wifi, &mut station, &candidate) {
+            Ok(_) => return Ok(wifi),
+            Err(e) => info!("Known access point failed ({}), scanning", e),
+        }
+    }
 
-        // unsafe {
-        //     esp_wifi_set_max_tx_power(config.get_tx_power());
-        // }
+    *last_ap() = None;
 
-        match wifi.start() {
-            Ok(_) => (),
-            Err(e) => {
-                if i == 5 {
-                    return Err(e.into());
-                }
-                continue;
-            }
+    let scanned: Vec<ScannedAp> = wifi
+        .scan()?
+        .iter()
+        .map(|ap| ScannedAp {
+            ssid: ap.ssid.to_string(),
+            bssid: ap.bssid,
+            channel: ap.channel,
+            rssi: ap.signal_strength,
+            auth: ap.auth_method.and_then(to_wifi_auth),
+        })
+        .collect();
+
+    let mut auth_error = None;
+
+    for candidate in connection_order(&networks, &scanned) {
+        // Do not try a connection that cannot work
+        if let Err(e) = candidate.check_auth() {
+            log::error!("{}", e);
+            auth_error = Some(e);
+            continue;
         }
-        info!("Wifi started");
 
-        match wifi.connect() {
-            Ok(_) => (),
-            Err(e) => {
-                if i == 5 {
-                    return Err(e.into());
-                }
-                continue;
+        for i in 1..=ATTEMPTS_PER_NETWORK {
+            log::info!(
+                "Wifi connection to '{}' ({}) attempt #{i}",
+                candidate.network.ssid,
+                candidate.auth()
+            );
+
+            match try_connect(&mut wifi, &mut station, &candidate) {
+                Ok(_) => return Ok(wifi),
+                Err(e) => log::warn!("Failed: {e}"),
             }
         }
-        info!("Wifi connected");
+    }
 
-        match wifi.wait_netif_up() {
-            Ok(_) => (),
-            Err(e) => {
-                if i == 5 {
-                    return Err(e.into());
-                }
-                continue;
+    match auth_error {
+        Some(e) => Err(e.into()),
+        None => Err(StringError("Failed to connect to every saved Wi-Fi network").into()),
+    }
+}
+
+/// Security of a scanned access point, `None` when not supported
+fn to_wifi_auth(auth: AuthMethod) -> Option<WifiAuth> {
+    match auth {
+        AuthMethod::None => Some(WifiAuth::Open),
+        AuthMethod::WPA | AuthMethod::WPAWPA2Personal => Some(WifiAuth::WpaWpa2),
+        AuthMethod::WPA2Personal => Some(WifiAuth::Wpa2),
+        AuthMethod::WPA2WPA3Personal => Some(WifiAuth::Wpa2Wpa3),
+        AuthMethod::WPA3Personal => Some(WifiAuth::Wpa3),
+        AuthMethod::WEP | AuthMethod::WPA2Enterprise | AuthMethod::WAPIPersonal => None,
+    }
+}
+
+/// Weakest security the station accepts
+fn to_auth_method(auth: WifiAuth) -> AuthMethod {
+    match auth {
+        WifiAuth::Open => AuthMethod::None,
+        WifiAuth::WpaWpa2 => AuthMethod::WPA,
+        WifiAuth::Auto | WifiAuth::Wpa2 => AuthMethod::WPA2Personal,
+        WifiAuth::Wpa2Wpa3 => AuthMethod::WPA2WPA3Personal,
+        WifiAuth::Wpa3 => AuthMethod::WPA3Personal,
+    }
+}
+
+/// Addressing of the station interface
+struct Station {
+    /// Static IP configuration of the first saved network
+    static_ip: Option<StaticIp>,
+    /// `true` while the interface uses [`Station::static_ip`], else DHCP
+    static_active: bool,
+}
+
+impl Station {
+    /// Use the static IP configuration for the first saved network and DHCP
+    /// for the others, which may be on other subnets.
+    fn select(&mut self, wifi: &mut BlockingWifi<EspWifi>, network: usize) -> anyhow::Result<()> {
+        let static_ip = self.static_ip.filter(|_| network == 0);
+
+        if static_ip.is_some() == self.static_active {
+            return Ok(());
+        }
+
+        let netif = match static_ip {
+            // A fixed address skips the DHCP exchange, one of the slowest steps
+            Some(static_ip) => {
+                info!(
+                    "Wifi static IP {}/{}, DNS {}",
+                    static_ip.ip, static_ip.prefix_len, static_ip.dns
+                );
+
+                EspNetif::new_with_conf(&NetifConfiguration {
+                    ip_configuration: ipv4::Configuration::Client(
+                        ipv4::ClientConfiguration::Fixed(ipv4::ClientSettings {
+                            ip: static_ip.ip,
+                            subnet: Subnet {
+                                gateway: static_ip.gateway,
+                                mask: Mask(static_ip.prefix_len),
+                            },
+                            dns: Some(static_ip.dns),
+                            secondary_dns: None,
+                        }),
+                    ),
+                    ..NetifConfiguration::wifi_default_client()
+                })?
             }
+            None => EspNetif::new(NetifStack::Sta)?,
+        };
+
+        wifi.wifi_mut().swap_netif_sta(netif)?;
+        self.static_active = static_ip.is_some();
+
+        Ok(())
+    }
+
+    fn addressing(&self) -> &'static str {
+        if self.static_active {
+            "static"
+        } else {
+            "DHCP"
         }
-        info!("Wifi netif up");
-        break;
     }
+}
 
-    Ok(wifi)
+/// Connect to `candidate` and remember its access point for the next
+/// wake-up.
+fn try_connect(
+    wifi: &mut BlockingWifi<EspWifi>,
+    station: &mut Station,
+    candidate: &Candidate,
+) -> anyhow::Result<()> {
+    let _ = wifi.disconnect();
+
+    station.select(wifi, candidate.index)?;
+    let addressing = station.addressing();
+
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+        ssid: candidate.network.ssid.as_str().try_into().unwrap(),
+        bssid: candidate.bssid,
+        auth_method: to_auth_method(candidate.auth()),
+        password: candidate.network.password.as_str().try_into().unwrap(),
+        channel: candidate.channel,
+    }))?;
+
+    // unsafe {
+    //     esp_wifi_set_max_tx_power(config.get_tx_power());
+    // }
+
+    let start = Instant::now();
+
+    wifi.connect()?;
+    info!(
+        "Wifi connected ({}) in {} ms",
+        addressing,
+        start.elapsed().as_millis()
+    );
+
+    wifi.wait_netif_up()?;
+    info!(
+        "Wifi netif up ({}) in {} ms",
+        addressing,
+        start.elapsed().as_millis()
+    );
+
+    let mut ap_info = wifi_ap_record_t::default();
+    if esp!(unsafe { esp_wifi_sta_get_ap_info(&mut ap_info) }).is_ok() {
+        *last_ap() = Some(CachedAp {
+            network: candidate.index,
+            bssid: ap_info.bssid,
+            channel: ap_info.primary,
+            auth: candidate.auth(),
+        });
+    }
+
+    Ok(())
 }
 
 pub fn create_ap<'a>(
+    config: &NvsConfiguration,
     modem: impl Peripheral<P = Modem> + 'a,
 ) -> anyhow::Result<BlockingWifi<EspWifi<'a>>> {
     let sys_loop = EspSystemEventLoop::take()?;
@@ -102,9 +317,12 @@ pub fn create_ap<'a>(
         EspNetif::new_with_conf(&NetifConfiguration {
             ip_configuration: ipv4::Configuration::Router(ipv4::RouterConfiguration {
                 subnet: Subnet {
-                    gateway: Ipv4Addr::from_str("192.168.70.1")?,
+                    gateway: AP_ADDRESS,
                     mask: Mask(24),
                 },
+                // Clients resolve every name through the captive portal DNS
+                dns: Some(AP_ADDRESS),
+                secondary_dns: None,
                 ..Default::default()
             }),
             ..NetifConfiguration::wifi_default_router()
@@ -123,20 +341,25 @@ pub fn create_ap<'a>(
 
     esp!(unsafe { esp_wifi_set_country(&cc) })?;
 
+    let ssid = ap_ssid(config);
+
     let wifi_configuration = Configuration::Mixed(
         ClientConfiguration {
             ..Default::default()
         },
         AccessPointConfiguration {
-            ssid: "ESP Config".try_into().unwrap(),
+            ssid: ssid.as_str().try_into().unwrap(),
             ssid_hidden: false,
-            auth_method: AuthMethod::None,
+            auth_method: AuthMethod::WPA2Personal,
+            password: config.get_ap_password().as_str().try_into().unwrap(),
             max_connections: 5,
             channel: 1,
             ..Default::default()
         },
     );
 
+    log::info!("Settings access point '{}'", ssid);
+
     log::info!("Set configuration");
     wifi.set_configuration(&wifi_configuration)?;
 